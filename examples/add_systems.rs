@@ -8,7 +8,7 @@ fn main() -> AppExit {
         // try adding and removing systems from here! make whole new ones!
         .with_hot_patch(|app: &mut App| {
             // StartupRerunHotPatch is like Startup, but will rerun on hot-reload.
-            // You need the #[hot(hot_patch_signature = true)] macro to auto-despawn entities spawned in it!
+            // Entities spawned in it are despawned automatically before it reruns.
             app.add_systems(StartupRerunHotPatch, spawn_ui);
             // All other systems do not require `#[hot]`.
             // Try writing, adding, and removing new ones here at runtime!
@@ -21,34 +21,28 @@ fn main() -> AppExit {
         .run()
 }
 
-#[hot(hot_patch_signature = true)]
 fn spawn_ui(mut commands: Commands) {
-    commands.queue(|world: &mut World| {
-        // Currently bevy forgets to do `track_caller` on `commands.spawn` so to
-        // auto-despawn entities spawned inside a StartupRerunHotPatch schedule
-        // we need to call spawn on `world` instead.
-        world.spawn((
-            Node {
-                // You can change the `Node` however you want at runtime
-                position_type: PositionType::Absolute,
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Center,
-                flex_direction: FlexDirection::Column,
-                row_gap: Val::Px(20.0),
-                ..default()
-            },
-            children![
-                Text::new("Press space to change the text below:"),
-                (
-                    Text::new("(no button pressed yet, or this system was reset)"),
-                    InfoLabel
-                ),
-            ],
-        ));
-        world.spawn(Camera2d);
-    });
+    commands.spawn((
+        Node {
+            // You can change the `Node` however you want at runtime
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(20.0),
+            ..default()
+        },
+        children![
+            Text::new("Press space to change the text below:"),
+            (
+                Text::new("(no button pressed yet, or this system was reset)"),
+                InfoLabel
+            ),
+        ],
+    ));
+    commands.spawn(Camera2d);
 }
 
 fn print_hello() {
@@ -7,7 +7,7 @@ fn main() -> AppExit {
         .add_plugins(SimpleSubsecondPlugin::default())
         .add_systems(Startup, setup)
         .add_systems(Startup, register_components)
-        .add_systems(Update, print_components)
+        .add_systems(Update, (print_components, log_migrations))
         .run()
 }
 
@@ -35,6 +35,7 @@ fn register_components(registry: Res<AppTypeRegistry>) {
 // - Rename them
 // - Add a field
 // - Remove a field
+// - Add a field with `#[hot_migrate(default = ...)]` to choose its value for existing entities
 
 #[derive(Debug, Reflect, Component, Default, HotPatchMigrate)]
 #[reflect(Component, Default, HotPatchMigrate)]
@@ -48,3 +49,17 @@ struct Player {
 fn print_components(player: Single<&Player>, mut text: Single<&mut Text>) {
     text.0 = format!("Player: {:#?}", player.into_inner());
 }
+
+fn log_migrations(mut migrations: EventReader<ComponentMigrated>) {
+    for migration in migrations.read() {
+        info!(
+            "Migrated {} entities from {} to {}. Added: {:?}, removed: {:?}, failed: {:?}",
+            migration.entities.len(),
+            migration.from_name,
+            migration.to_name,
+            migration.fields_added,
+            migration.fields_removed,
+            migration.fields_failed,
+        );
+    }
+}
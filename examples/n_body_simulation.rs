@@ -55,32 +55,23 @@ struct GameAssets {
     attractor_material: Handle<ColorMaterial>,
 }
 
-#[derive(Component)]
-struct Setup;
-
-#[hot(rerun_on_hot_patch = true)]
+// `despawn_spawned` despawns all entities spawned by the previous run of `setup`
+// so that hot-patching does not spawn them again
+#[hot(rerun_on_hot_patch = true, despawn_spawned = true)]
 fn setup(
-    previous_setup: Query<Entity, With<Setup>>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    // Clear all entities that were spawned on `Startup` so that
-    // hot-patching does not spawn them again
-    for entity in previous_setup.iter() {
-        commands.entity(entity).despawn();
-    }
-
     // Spawn a canvas to register our clicks
     commands
-        .spawn((
-            Setup,
-            Mesh3d(meshes.add(Plane3d::new(Vec3::Z, Vec2::splat(1000.0)))),
+        .spawn(Mesh3d(
+            meshes.add(Plane3d::new(Vec3::Z, Vec2::splat(1000.0))),
         ))
         .observe(react_to_click);
 
     // Spawn a camera to view the scene
-    commands.spawn((Setup, Camera2d));
+    commands.spawn(Camera2d);
 
     // Define our assets.
     // Try changing these values at runtime and spawning new planets and attractors!
@@ -94,7 +85,6 @@ fn setup(
     // Spawn a text nodes to display instructions
     // Try changing the text at runtime!
     commands.spawn((
-        Setup,
         Node {
             flex_direction: FlexDirection::Column,
             ..default()
@@ -8,19 +8,11 @@ fn main() -> AppExit {
         .run()
 }
 
-#[derive(Component)]
-struct Setup;
-
-#[hot(rerun_on_hot_patch = true)]
-fn setup(previous_setup: Query<Entity, With<Setup>>, mut commands: Commands) {
-    // Clear all entities that were spawned on `Startup` so that
-    // hot-patching does not spawn them again
-    for entity in previous_setup.iter() {
-        commands.entity(entity).despawn();
-    }
-
+// `despawn_spawned` despawns everything the previous run of the system spawned
+// so that hot-patching does not spawn it again
+#[hot(rerun_on_hot_patch = true, despawn_spawned = true)]
+fn setup(mut commands: Commands) {
     commands.spawn((
-        Setup,
         Node {
             // You can change the `Node` however you want at runtime
             position_type: PositionType::Absolute,
@@ -37,7 +29,7 @@ fn setup(previous_setup: Query<Entity, With<Setup>>, mut commands: Commands) {
             Text::new("Try adding new texts below"),
         ],
     ));
-    commands.spawn((Setup, Camera2d));
+    commands.spawn(Camera2d);
 
     commands.insert_resource(UiDebugOptions {
         // Set this to `true` to see the UI debug overlay. Try changing it at runtime!
@@ -2,11 +2,11 @@
 #![warn(missing_docs)]
 
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
-use syn::spanned::Spanned;
+use quote::{format_ident, quote, quote_spanned};
+use syn::ext::IdentExt as _;
 use syn::{
-    DeriveInput, FnArg, Ident, ItemFn, LitBool, Pat, PatIdent, ReturnType, Token, Type, TypePath,
-    TypeReference,
+    Data, DeriveInput, Expr, FnArg, Ident, ItemFn, LitBool, Pat, PatIdent, ReturnType, Token, Type,
+    TypePath, TypeReference,
     parse::{Parse, ParseStream},
     parse_macro_input,
 };
@@ -14,12 +14,14 @@ use syn::{
 struct HotArgs {
     rerun_on_hot_patch: Option<bool>,
     hot_patch_signature: Option<bool>,
+    despawn_spawned: Option<bool>,
 }
 
 impl Parse for HotArgs {
     fn parse(input: ParseStream) -> std::result::Result<HotArgs, syn::Error> {
         let mut rerun_on_hot_patch = None;
         let mut hot_patch_signature = None;
+        let mut despawn_spawned = None;
 
         while !input.is_empty() {
             let ident: Ident = input.parse()?;
@@ -31,6 +33,9 @@ impl Parse for HotArgs {
             } else if ident == "hot_patch_signature" {
                 let value: LitBool = input.parse()?;
                 hot_patch_signature = Some(value.value);
+            } else if ident == "despawn_spawned" {
+                let value: LitBool = input.parse()?;
+                despawn_spawned = Some(value.value);
             } else {
                 return Err(syn::Error::new_spanned(ident, "Unknown attribute key"));
             }
@@ -43,11 +48,21 @@ impl Parse for HotArgs {
         Ok(HotArgs {
             rerun_on_hot_patch,
             hot_patch_signature,
+            despawn_spawned,
         })
     }
 }
 
 /// Annotate your systems with `#[hot]` to enable hotpatching for them.
+///
+/// Accepts the following options:
+/// - `rerun_on_hot_patch = true`: rerun the system after it was hot patched.
+/// - `hot_patch_signature = true`: allow changing the system's parameters at runtime.
+/// - `despawn_spawned = true`: together with `rerun_on_hot_patch`, despawn the entities spawned
+///   and remove the resources inserted by the previous run of the system before rerunning it.
+///
+/// Systems returning `()` or `Result` are disabled instead of crashing the app when they panic,
+/// if `SimpleSubsecondPlugin::catch_panics` is enabled.
 #[proc_macro_attribute]
 pub fn hot(attr: TokenStream, item: TokenStream) -> TokenStream {
     // Parse the attribute as a Meta
@@ -58,6 +73,15 @@ pub fn hot(attr: TokenStream, item: TokenStream) -> TokenStream {
     };
     let rerun_on_hot_patch = args.rerun_on_hot_patch.unwrap_or(false);
     let hot_patch_signature = args.hot_patch_signature.unwrap_or(false);
+    let despawn_spawned = args.despawn_spawned.unwrap_or(false);
+    if despawn_spawned && !rerun_on_hot_patch {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`despawn_spawned` requires `rerun_on_hot_patch = true`",
+        )
+        .to_compile_error()
+        .into();
+    }
 
     let input_fn = syn::parse::<ItemFn>(item.clone());
     let input_fn = match input_fn {
@@ -77,12 +101,6 @@ pub fn hot(attr: TokenStream, item: TokenStream) -> TokenStream {
     let hotpatched_fn = format_ident!("__{}_hotpatched", original_fn_name);
     let original_wrapper_fn = format_ident!("__{}_original", original_fn_name);
 
-    let newlines = if let Some(source_text) = block.span().unwrap().source_text() {
-        source_text.chars().filter(|ch| *ch == '\n').count() as u32
-    } else {
-        0
-    };
-
     // Capture parameter types, names, and mutability
     let mut param_types = Vec::new();
     let mut param_idents = Vec::new();
@@ -130,7 +148,76 @@ pub fn hot(attr: TokenStream, item: TokenStream) -> TokenStream {
         ::bevy_simple_subsecond_system::dioxus_devtools::subsecond::HotFn::current(#hotpatched_fn #maybe_generics)
     };
 
+    // Panics can only be caught if there is a value to return instead
+    let disabled_output = match original_output {
+        ReturnType::Default => Some(quote! { () }),
+        _ if is_result_unit(original_output) => Some(quote! { Ok(()) }),
+        _ => None,
+    };
+    let call_guarded = |args: proc_macro2::TokenStream| match &disabled_output {
+        Some(disabled_output) => quote! {
+            match ::bevy_simple_subsecond_system::__macros_internal::__call_guarded(
+                ::core::any::type_name_of_val(&#original_fn_name #maybe_generics),
+                &mut #hot_fn,
+                #args,
+            ) {
+                Some(result) => result,
+                None => #disabled_output,
+            }
+        },
+        None => quote! {
+            ::bevy_simple_subsecond_system::__macros_internal::__call(
+                ::core::any::type_name_of_val(&#original_fn_name #maybe_generics),
+                &mut #hot_fn,
+                #args,
+            )
+        },
+    };
+
+    // Add the system to the `HotSystemRegistry` the first time it runs.
+    // Its pointer is checked there after every hot patch, and it is rerun if it changed.
+    let mode = if rerun_on_hot_patch {
+        format_ident!("RerunOnHotPatch")
+    } else if hot_patch_signature {
+        format_ident!("HotPatchSignature")
+    } else {
+        format_ident!("Plain")
+    };
+    let first_line = quote_spanned! {sig.fn_token.span=> ::core::line!() };
+    let last_line = quote_spanned! {block.brace_token.span.close()=> ::core::line!() };
+    let rerun = if rerun_on_hot_patch {
+        quote! {
+            Some(|world: &mut ::bevy_simple_subsecond_system::__macros_internal::World| {
+                // TODO: we simply ignore the `Result` here, but we should be propagating it
+                let _ = #original_fn_name #maybe_generics(world);
+            })
+        }
+    } else {
+        quote! { None }
+    };
+    let register_call = quote! {
+        ::bevy_simple_subsecond_system::__macros_internal::__register_hot_system(
+            ::core::any::type_name_of_val(&#original_fn_name #maybe_generics),
+            ::core::file!(),
+            (#first_line, #last_line),
+            ::bevy_simple_subsecond_system::__macros_internal::HotSystemMode::#mode,
+            || #hot_fn.ptr_address(),
+            #rerun,
+        )
+    };
+    // All instantiations of a generic system share its statics, so they register every time they run instead
+    // and the registry skips the ones it knows already, without taking a lock
+    let register = if generics.params.is_empty() {
+        quote! {
+            static __REGISTERED: ::std::sync::Once = ::std::sync::Once::new();
+            __REGISTERED.call_once(|| #register_call);
+        }
+    } else {
+        quote! { #register_call; }
+    };
+
     if !hot_patch_signature && !rerun_on_hot_patch {
+        let call_hot_fn = call_guarded(quote! { (#(#param_idents,)*) });
         let result = quote! {
             #[cfg(any(target_family = "wasm", not(debug_assertions)))]
             #vis fn #original_fn_name #impl_generics(#inputs) #where_clause #original_output {
@@ -141,7 +228,8 @@ pub fn hot(attr: TokenStream, item: TokenStream) -> TokenStream {
             #[cfg(all(not(target_family = "wasm"), debug_assertions))]
             #[allow(unused_mut)]
             #vis fn #original_fn_name #impl_generics(#inputs) #where_clause #original_output {
-                #hot_fn.call((#(#param_idents,)*))
+                #register
+                #call_hot_fn
             }
 
 
@@ -153,17 +241,17 @@ pub fn hot(attr: TokenStream, item: TokenStream) -> TokenStream {
         return result.into();
     }
 
-    let maybe_run_call = if rerun_on_hot_patch {
+    let call_hot_fn = call_guarded(quote! { (world,) });
+    let call_hot_fn = if despawn_spawned {
         quote! {
-            let name = ::bevy_simple_subsecond_system::__macros_internal::IntoSystem::into_system(#original_fn_name #maybe_generics).name();
-            ::bevy_simple_subsecond_system::__macros_internal::debug!("Hot-patched and rerunning system {name}");
-            #hot_fn.call((world,))
+            ::bevy_simple_subsecond_system::__macros_internal::__run_scoped(
+                world,
+                ::core::any::type_name_of_val(&#original_fn_name #maybe_generics),
+                |world| #call_hot_fn,
+            )
         }
     } else {
-        quote! {
-            let name = ::bevy_simple_subsecond_system::__macros_internal::IntoSystem::into_system(#original_fn_name #maybe_generics).name();
-            bevy::prelude::debug!("Hot-patched system {name}");
-        }
+        call_hot_fn
     };
 
     let early_return = if is_result_unit(original_output) {
@@ -179,17 +267,11 @@ pub fn hot(attr: TokenStream, item: TokenStream) -> TokenStream {
     let hotpatched_fn_definition = match has_single_world_param(sig) {
         WorldParam::Mut | WorldParam::Ref => quote! {
             #vis fn #hotpatched_fn #impl_generics(world: &mut ::bevy_simple_subsecond_system::__macros_internal::World) #where_clause #original_output {
-                if let Some(mut reload_positions) = world.get_resource_mut::<::bevy_simple_subsecond_system::__macros_internal::__ReloadPositions>() {
-                    reload_positions.insert((file!(), line!(), line!() + #newlines));
-                }
                 #original_wrapper_fn #maybe_generics(world)
             }
         },
         WorldParam::None => quote! {
             #vis fn #hotpatched_fn #impl_generics(world: &mut ::bevy_simple_subsecond_system::__macros_internal::World) #where_clause #original_output {
-                if let Some(mut reload_positions) = world.get_resource_mut::<::bevy_simple_subsecond_system::__macros_internal::__ReloadPositions>() {
-                    reload_positions.insert((file!(), line!(), line!() + #newlines));
-                }
                 use ::bevy_simple_subsecond_system::__macros_internal::SystemState;
                 let mut __system_state: SystemState<(#(#param_types),*)> = SystemState::new(world);
                 let __unsafe_world = world.as_unsafe_world_cell_readonly();
@@ -222,39 +304,8 @@ pub fn hot(attr: TokenStream, item: TokenStream) -> TokenStream {
         // Outer entry point: stable ABI, hot-reload safe
         #[cfg(all(not(target_family = "wasm"), debug_assertions))]
         #vis fn #original_fn_name #impl_generics(world: &mut ::bevy_simple_subsecond_system::__macros_internal::World) #where_clause #original_output {
-            use std::any::Any as _;
-            let type_id = #hotpatched_fn #maybe_generics.type_id();
-            let contains_system = world.get_resource::<::bevy_simple_subsecond_system::__macros_internal::__HotPatchedSystems>().unwrap().0.contains_key(&type_id);
-            if !contains_system {
-                let hot_fn_ptr = #hot_fn.ptr_address();
-                let system = move |world: &mut ::bevy_simple_subsecond_system::__macros_internal::World| {
-                    let needs_update = {
-                        let mut hot_patched_systems = world.get_resource_mut::<::bevy_simple_subsecond_system::__macros_internal::__HotPatchedSystems>().unwrap();
-                        let mut hot_patched_system = hot_patched_systems.0.get_mut(&type_id).unwrap();
-                        hot_patched_system.current_ptr = #hot_fn.ptr_address();
-                        let needs_update = hot_patched_system.current_ptr != hot_patched_system.last_ptr;
-                        hot_patched_system.last_ptr = hot_patched_system.current_ptr;
-                        needs_update
-                    };
-                    if !needs_update {
-                        return;
-                    }
-                    // TODO: we simply ignore the `Result` here, but we should be propagating it
-                    let _ = {#maybe_run_call};
-                };
-                world.resource_mut::<::bevy_simple_subsecond_system::__macros_internal::Schedules>()
-                  .add_systems(
-                    ::bevy_simple_subsecond_system::__macros_internal::PreUpdate,
-                    system.in_set(::bevy_simple_subsecond_system::SimpleSubsecondSystemSet::UpdateFunctionPtrs)
-                );
-                let system = ::bevy_simple_subsecond_system::__macros_internal::__HotPatchedSystem {
-                    current_ptr: hot_fn_ptr,
-                    last_ptr: hot_fn_ptr,
-                };
-                world.get_resource_mut::<::bevy_simple_subsecond_system::__macros_internal::__HotPatchedSystems>().unwrap().0.insert(type_id, system);
-            }
-
-            #hot_fn.call((world,))
+            #register
+            #call_hot_fn
         }
 
         // Hotpatched version with stable signature
@@ -360,19 +411,96 @@ fn is_result_unit(output: &ReturnType) -> bool {
 
 /// Derive `HotPatchMigrate` and reflect it for your struct to be migrated
 /// when a hot patch happens. You will also need to implement/derive and
-/// reflect `Component` and `Default`.
-#[proc_macro_derive(HotPatchMigrate)]
+/// reflect `Component`.
+///
+/// Fields that cannot be taken from the previous definition use the value of
+/// `#[hot_migrate(default = ...)]`, or else the reflected `Default` of the struct.
+///
+/// Generic structs are supported. Every monomorphization that is registered is migrated.
+#[proc_macro_derive(HotPatchMigrate, attributes(hot_migrate))]
 pub fn derive_hot_patch_migrate(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = &input.ident;
 
+    let default_fields = match hot_migrate_defaults(&input) {
+        Ok(default_fields) => default_fields,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let default_field_fn = if default_fields.is_empty() {
+        quote! {}
+    } else {
+        let arms = default_fields.iter().map(|(field, ty, default)| {
+            quote! {
+                #field => {
+                    let value: #ty = #default;
+                    ::core::option::Option::Some(::std::boxed::Box::new(value))
+                }
+            }
+        });
+        quote! {
+            fn default_field(field: &str) -> ::core::option::Option<::std::boxed::Box<dyn ::bevy_simple_subsecond_system::__macros_internal::PartialReflect>> {
+                match field {
+                    #(#arms)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+    };
+
+    // Bound on `Self` instead of the generic parameters, so that every monomorphization
+    // that is a reflected component can be migrated.
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    where_clause.predicates.push(syn::parse_quote! {
+        Self: ::core::any::Any
+            + ::bevy_simple_subsecond_system::__macros_internal::Component
+            + ::bevy_simple_subsecond_system::__macros_internal::FromReflect
+    });
+    for (_, ty, _) in &default_fields {
+        where_clause.predicates.push(syn::parse_quote! {
+            #ty: ::bevy_simple_subsecond_system::__macros_internal::PartialReflect
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
     let expanded = quote! {
-        impl ::bevy_simple_subsecond_system::migration::HotPatchMigrate for #name {
+        impl #impl_generics ::bevy_simple_subsecond_system::migration::HotPatchMigrate for #name #ty_generics #where_clause {
             fn current_type_id() -> ::core::any::TypeId {
                 ::bevy_simple_subsecond_system::dioxus_devtools::subsecond::HotFn::current(|| ::core::any::TypeId::of::<Self>()).call(())
             }
+
+            #default_field_fn
         }
     };
 
     TokenStream::from(expanded)
 }
+
+/// Collects the field names, types and expressions of all `#[hot_migrate(default = ...)]` attributes.
+/// Tuple struct fields are named by their index.
+fn hot_migrate_defaults(input: &DeriveInput) -> syn::Result<Vec<(String, Type, Expr)>> {
+    let Data::Struct(data) = &input.data else {
+        return Ok(Vec::new());
+    };
+    let mut defaults = Vec::new();
+    for (index, field) in data.fields.iter().enumerate() {
+        for attr in &field.attrs {
+            if !attr.path().is_ident("hot_migrate") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if !meta.path.is_ident("default") {
+                    return Err(meta.error("Unknown attribute key"));
+                }
+                let default: Expr = meta.value()?.parse()?;
+                let name = match &field.ident {
+                    Some(ident) => ident.unraw().to_string(),
+                    None => index.to_string(),
+                };
+                defaults.push((name, field.ty.clone(), default));
+                Ok(())
+            })?;
+        }
+    }
+    Ok(defaults)
+}
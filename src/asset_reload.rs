@@ -0,0 +1,61 @@
+//! Reloads the assets that the devserver reports as changed.
+//! Enabled by the `reload_assets` feature.
+
+use std::path::{Path, PathBuf};
+
+use bevy_app::{App, PreUpdate};
+use bevy_asset::{AssetPlugin, AssetServer, io::file::FileAssetReader};
+use bevy_ecs::prelude::*;
+use bevy_log::debug;
+
+use crate::HotAssetsChanged;
+
+/// Directories the changed asset paths are resolved against.
+#[derive(Resource, Debug)]
+struct AssetDirectories {
+    /// Relative paths reported by the devserver are relative to this directory.
+    base: PathBuf,
+    /// The directory of the default asset source, see [`AssetPlugin::file_path`].
+    root: PathBuf,
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    let Some(asset_plugin) = app.get_added_plugins::<AssetPlugin>().into_iter().next() else {
+        debug!("`AssetPlugin` was not added, changed assets will not be reloaded");
+        return;
+    };
+    let base = FileAssetReader::get_base_path();
+    let root = base.join(&asset_plugin.file_path);
+    app.insert_resource(AssetDirectories { base, root })
+        .add_systems(
+            PreUpdate,
+            reload_changed_assets.run_if(on_event::<HotAssetsChanged>),
+        );
+}
+
+fn reload_changed_assets(
+    mut events: EventReader<HotAssetsChanged>,
+    asset_server: Res<AssetServer>,
+    directories: Res<AssetDirectories>,
+) {
+    for path in events.read().flat_map(|event| &event.paths) {
+        let Some(asset_path) = asset_path(&directories, path) else {
+            debug!(
+                "Not reloading {}, as it is not in {}",
+                path.display(),
+                directories.root.display()
+            );
+            continue;
+        };
+        debug!("Reloading asset {}", asset_path.display());
+        asset_server.reload(asset_path);
+    }
+}
+
+/// The path of the changed asset relative to the asset directory, if it is inside of it.
+fn asset_path(directories: &AssetDirectories, path: &Path) -> Option<PathBuf> {
+    let path = directories.base.join(path);
+    path.strip_prefix(&directories.root)
+        .ok()
+        .map(Path::to_path_buf)
+}
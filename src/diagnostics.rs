@@ -0,0 +1,132 @@
+//! [Diagnostics](bevy_diagnostic) measuring how long hot patches take to apply.
+
+use core::time::Duration;
+
+use bevy_app::{App, Last, Plugin};
+use bevy_diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy_ecs::prelude::*;
+
+use crate::{HotPatched, migration::ComponentMigrated};
+
+/// Adds [`Diagnostic`]s measuring the cost of hot patches. Each diagnostic is measured
+/// in the frames in which the corresponding phase happened.
+///
+/// The phases are also traced with the spans `hot_patch_apply`, `hot_patch_section`,
+/// `hot_patch_schedule_rebuild`, `hot_patch_rerun` and `hot_patch_migrate`, e.g. for Tracy.
+///
+/// ```ignore
+/// App::new()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugins((SimpleSubsecondPlugin::default(), HotPatchDiagnosticsPlugin))
+///     .add_plugins(LogDiagnosticsPlugin::default())
+///     .run();
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct HotPatchDiagnosticsPlugin;
+
+impl HotPatchDiagnosticsPlugin {
+    /// Number of hot patches applied, including the generations restored by [`HotPatchHistory`](crate::history::HotPatchHistory).
+    pub const PATCH_COUNT: DiagnosticPath = DiagnosticPath::const_new("hot_patch/count");
+    /// Time taken to load a hot patch, in milliseconds.
+    pub const APPLY_TIME: DiagnosticPath = DiagnosticPath::const_new("hot_patch/apply_time");
+    /// Time taken to rerun the systems with `#[hot(rerun_on_hot_patch = true)]` after a hot patch, in milliseconds.
+    pub const RERUN_TIME: DiagnosticPath = DiagnosticPath::const_new("hot_patch/rerun_time");
+    /// Time taken to rerun the hot-patched sections of [`HotPatchedAppExt::with_hot_patch`](crate::hot_patched_app::HotPatchedAppExt::with_hot_patch)
+    /// and reinitialize their schedules, in milliseconds.
+    pub const SCHEDULE_REBUILD_TIME: DiagnosticPath =
+        DiagnosticPath::const_new("hot_patch/schedule_rebuild_time");
+    /// Time taken to migrate components after a hot patch, in milliseconds.
+    pub const MIGRATION_TIME: DiagnosticPath =
+        DiagnosticPath::const_new("hot_patch/migration_time");
+    /// Number of entities whose components were migrated after a hot patch.
+    pub const ENTITIES_MIGRATED: DiagnosticPath =
+        DiagnosticPath::const_new("hot_patch/entities_migrated");
+}
+
+impl Plugin for HotPatchDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        // Averaging over hot patches would hide slow ones
+        let diagnostic = |path| Diagnostic::new(path).with_smoothing_factor(0.0);
+        app.init_resource::<HotPatchTimings>()
+            .add_event::<HotPatched>()
+            .add_event::<ComponentMigrated>()
+            .register_diagnostic(diagnostic(Self::PATCH_COUNT).with_max_history_length(0))
+            .register_diagnostic(diagnostic(Self::APPLY_TIME).with_suffix("ms"))
+            .register_diagnostic(diagnostic(Self::RERUN_TIME).with_suffix("ms"))
+            .register_diagnostic(diagnostic(Self::SCHEDULE_REBUILD_TIME).with_suffix("ms"))
+            .register_diagnostic(diagnostic(Self::MIGRATION_TIME).with_suffix("ms"))
+            .register_diagnostic(diagnostic(Self::ENTITIES_MIGRATED))
+            .add_systems(Last, measure);
+    }
+}
+
+/// Time spent on the phases of hot patches since the diagnostics were last measured.
+/// Only recorded if the [`HotPatchDiagnosticsPlugin`] was added.
+#[derive(Resource, Default)]
+pub(crate) struct HotPatchTimings {
+    patch_count: usize,
+    apply: Option<Duration>,
+    rerun: Option<Duration>,
+    schedule_rebuild: Option<Duration>,
+}
+
+impl HotPatchTimings {
+    pub(crate) fn record_apply(&mut self, duration: Duration) {
+        *self.apply.get_or_insert_default() += duration;
+    }
+
+    pub(crate) fn record_rerun(&mut self, duration: Duration) {
+        *self.rerun.get_or_insert_default() += duration;
+    }
+
+    pub(crate) fn record_schedule_rebuild(&mut self, duration: Duration) {
+        *self.schedule_rebuild.get_or_insert_default() += duration;
+    }
+}
+
+fn measure(
+    mut diagnostics: Diagnostics,
+    mut timings: ResMut<HotPatchTimings>,
+    mut hot_patched: EventReader<HotPatched>,
+    mut migrated: EventReader<ComponentMigrated>,
+) {
+    let patches = hot_patched.read().count();
+    if patches > 0 {
+        timings.patch_count += patches;
+        let patch_count = timings.patch_count;
+        diagnostics.add_measurement(&HotPatchDiagnosticsPlugin::PATCH_COUNT, || {
+            patch_count as f64
+        });
+    }
+    let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    if let Some(apply) = timings.apply.take() {
+        diagnostics.add_measurement(&HotPatchDiagnosticsPlugin::APPLY_TIME, || {
+            milliseconds(apply)
+        });
+    }
+    if let Some(rerun) = timings.rerun.take() {
+        diagnostics.add_measurement(&HotPatchDiagnosticsPlugin::RERUN_TIME, || {
+            milliseconds(rerun)
+        });
+    }
+    if let Some(schedule_rebuild) = timings.schedule_rebuild.take() {
+        diagnostics.add_measurement(&HotPatchDiagnosticsPlugin::SCHEDULE_REBUILD_TIME, || {
+            milliseconds(schedule_rebuild)
+        });
+    }
+
+    let mut migration = None::<(Duration, usize)>;
+    for migrated in migrated.read().filter(|migrated| !migrated.dry_run) {
+        let (duration, entities) = migration.get_or_insert_default();
+        *duration += migrated.duration;
+        *entities += migrated.entities.len();
+    }
+    if let Some((duration, entities)) = migration {
+        diagnostics.add_measurement(&HotPatchDiagnosticsPlugin::MIGRATION_TIME, || {
+            milliseconds(duration)
+        });
+        diagnostics.add_measurement(&HotPatchDiagnosticsPlugin::ENTITIES_MIGRATED, || {
+            entities as f64
+        });
+    }
+}
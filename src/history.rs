@@ -0,0 +1,334 @@
+//! Undo and redo hot patches.
+
+use std::{collections::VecDeque, path::Path, time::Instant};
+
+use bevy_ecs::prelude::*;
+use bevy_log::{error, info, info_span};
+use dioxus_devtools::subsecond::JumpTable;
+
+use crate::diagnostics::HotPatchTimings;
+
+/// The hot patches applied since the app was launched, allowing you to go back to a previous version of the code:
+///
+/// ```ignore
+/// fn undo_on_f1(keys: Res<ButtonInput<KeyCode>>, mut history: ResMut<HotPatchHistory>) {
+///     if keys.just_pressed(KeyCode::F1) {
+///         history.undo();
+///     }
+/// }
+/// ```
+///
+/// Going to another generation sends [`HotPatched`](crate::HotPatched) as if that version had been patched in,
+/// so systems with `#[hot(rerun_on_hot_patch = true)]` rerun.
+/// Receiving a new patch after undoing discards the patches that could have been redone.
+/// Generations are never reused, so the new patch gets a generation of its own.
+///
+/// Only the last [`HotPatchHistory::limit`] patches are kept.
+#[derive(Resource, Debug)]
+pub struct HotPatchHistory {
+    /// The patches that can be restored, along with their generation, from oldest to newest.
+    patches: VecDeque<(usize, JumpTable)>,
+    /// The generation of the next patch received.
+    next_patch: usize,
+    /// Whether the code the app was launched with can still be restored.
+    launch_kept: bool,
+    /// The generation requested by `undo` and `redo`.
+    generation: usize,
+    /// The generation whose code is running.
+    applied: usize,
+    limit: usize,
+}
+
+impl Default for HotPatchHistory {
+    fn default() -> Self {
+        Self {
+            patches: VecDeque::new(),
+            next_patch: 1,
+            launch_kept: true,
+            generation: 0,
+            applied: 0,
+            limit: 16,
+        }
+    }
+}
+
+impl HotPatchHistory {
+    /// The generation of the running code. `0` is the code the app was launched with,
+    /// and every hot patch received gets the next generation.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The generation of the latest patch received.
+    pub fn latest_generation(&self) -> usize {
+        self.patches.back().map_or(0, |&(generation, _)| generation)
+    }
+
+    /// The oldest generation that can be restored.
+    pub fn oldest_generation(&self) -> usize {
+        // The code the app was launched with can be restored as long as no patch was forgotten
+        match self.patches.front() {
+            Some(&(generation, _)) if !self.launch_kept => generation,
+            _ => 0,
+        }
+    }
+
+    /// Whether [`HotPatchHistory::undo`] would do anything.
+    pub fn can_undo(&self) -> bool {
+        self.previous_generation().is_some()
+    }
+
+    /// Whether [`HotPatchHistory::redo`] would do anything.
+    pub fn can_redo(&self) -> bool {
+        self.next_generation().is_some()
+    }
+
+    /// Goes back to the previous generation of code at the end of the frame.
+    /// Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(generation) = self.previous_generation() else {
+            return false;
+        };
+        self.generation = generation;
+        true
+    }
+
+    /// Goes forward to the next generation of code at the end of the frame, after [`HotPatchHistory::undo`].
+    /// Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(generation) = self.next_generation() else {
+            return false;
+        };
+        self.generation = generation;
+        true
+    }
+
+    /// Iterates over the generations that can be restored, along with the library containing their patch.
+    /// Generation `0`, the code the app was launched with, is not included.
+    pub fn patches(&self) -> impl Iterator<Item = (usize, &Path)> {
+        self.patches
+            .iter()
+            .map(|(generation, jump_table)| (*generation, jump_table.lib.as_path()))
+    }
+
+    /// The maximum number of patches kept. Defaults to 16.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Sets the maximum number of patches kept, forgetting the oldest ones if necessary.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+        self.forget_oldest();
+    }
+
+    /// Records a patch that was just applied.
+    pub(crate) fn push(&mut self, jump_table: JumpTable) {
+        // The patches that could have been redone are based on older code
+        let generation = self.generation;
+        self.patches.retain(|&(patch, _)| patch <= generation);
+        self.patches.push_back((self.next_patch, jump_table));
+        self.generation = self.next_patch;
+        self.applied = self.generation;
+        self.next_patch += 1;
+        self.forget_oldest();
+    }
+
+    /// The restorable generation right before the requested one.
+    fn previous_generation(&self) -> Option<usize> {
+        let previous = self
+            .patches
+            .iter()
+            .rev()
+            .map(|&(generation, _)| generation)
+            .find(|&generation| generation < self.generation);
+        previous.or((self.launch_kept && self.generation > 0).then_some(0))
+    }
+
+    /// The restorable generation right after the requested one.
+    fn next_generation(&self) -> Option<usize> {
+        self.patches
+            .iter()
+            .map(|&(generation, _)| generation)
+            .find(|&generation| generation > self.generation)
+    }
+
+    fn forget_oldest(&mut self) {
+        while self.patches.len() > self.limit {
+            self.patches.pop_front();
+            self.launch_kept = false;
+        }
+        self.generation = self.generation.max(self.oldest_generation());
+    }
+
+    /// The jump table that restores `generation`.
+    fn jump_table(&self, generation: usize) -> Option<JumpTable> {
+        if generation == 0 {
+            // An empty table makes every function call the code the app was launched with.
+            // `apply_patch` still needs a library to load, so reuse one that was loaded already.
+            let (_, first) = self.patches.front()?;
+            return Some(JumpTable {
+                map: Default::default(),
+                ..first.clone()
+            });
+        }
+        self.patches
+            .iter()
+            .find(|&&(patch, _)| patch == generation)
+            .map(|(_, jump_table)| jump_table.clone())
+    }
+}
+
+/// Applies the generation requested by [`HotPatchHistory::undo`] and [`HotPatchHistory::redo`].
+pub(crate) fn apply_requested_generation(
+    mut history: ResMut<HotPatchHistory>,
+    mut hot_patched: EventWriter<crate::HotPatched>,
+    mut patch_failed: EventWriter<crate::HotPatchFailed>,
+    timings: Option<ResMut<HotPatchTimings>>,
+) {
+    if history.generation == history.applied {
+        return;
+    }
+    let generation = history.generation;
+    let Some(jump_table) = history.jump_table(generation) else {
+        history.generation = history.applied;
+        return;
+    };
+    let _span = info_span!("hot_patch_apply", generation).entered();
+    let start = Instant::now();
+    // SAFETY: The jump table was validated and applied before,
+    // but anything using the updated jump table is still unsafe.
+    match unsafe { dioxus_devtools::subsecond::apply_patch(jump_table) } {
+        Ok(()) => {
+            if let Some(mut timings) = timings {
+                timings.record_apply(start.elapsed());
+            }
+            info!("Restored hot patch generation {generation}");
+            history.applied = generation;
+            hot_patched.write_default();
+        }
+        Err(err) => {
+            let reason = crate::HotPatchFailedReason::ApplyFailed(err.to_string());
+            error!("Failed to restore hot patch generation {generation}: {reason}");
+            history.generation = history.applied;
+            patch_failed.write(crate::HotPatchFailed { reason });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use dioxus_devtools::subsecond::JumpTable;
+
+    use super::HotPatchHistory;
+
+    fn patch(name: &str) -> JumpTable {
+        JumpTable {
+            lib: PathBuf::from(name),
+            map: Default::default(),
+            aslr_reference: 0,
+            new_base_address: 0,
+            ifunc_count: 0,
+        }
+    }
+
+    fn patches(history: &HotPatchHistory) -> Vec<(usize, &Path)> {
+        history.patches().collect()
+    }
+
+    #[test]
+    fn push_increases_the_generation() {
+        let mut history = HotPatchHistory::default();
+        assert_eq!(history.generation(), 0);
+        assert_eq!(history.latest_generation(), 0);
+        assert!(!history.can_undo());
+        assert!(!history.can_redo());
+        assert_eq!(history.jump_table(0), None);
+
+        history.push(patch("a"));
+        history.push(patch("b"));
+        assert_eq!(history.generation(), 2);
+        assert_eq!(history.latest_generation(), 2);
+        assert_eq!(history.oldest_generation(), 0);
+        assert_eq!(
+            patches(&history),
+            [(1, Path::new("a")), (2, Path::new("b"))]
+        );
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_generations() {
+        let mut history = HotPatchHistory::default();
+        history.push(patch("a"));
+        history.push(patch("b"));
+
+        assert!(history.undo());
+        assert_eq!(history.generation(), 1);
+        assert!(history.undo());
+        assert_eq!(history.generation(), 0);
+        assert!(!history.undo());
+        // The launch code is restored by an empty table with a library that was loaded already
+        let launch = history.jump_table(0).unwrap();
+        assert!(launch.map.is_empty());
+        assert_eq!(launch.lib, Path::new("a"));
+
+        assert!(history.redo());
+        assert!(history.redo());
+        assert_eq!(history.generation(), 2);
+        assert!(!history.redo());
+        assert_eq!(history.jump_table(2).unwrap().lib, Path::new("b"));
+    }
+
+    #[test]
+    fn push_after_undo_discards_redo_without_reusing_generations() {
+        let mut history = HotPatchHistory::default();
+        history.push(patch("a"));
+        history.push(patch("b"));
+        history.push(patch("c"));
+        history.undo();
+        history.undo();
+
+        history.push(patch("d"));
+        assert_eq!(history.generation(), 4);
+        assert_eq!(history.latest_generation(), 4);
+        assert!(!history.can_redo());
+        assert_eq!(
+            patches(&history),
+            [(1, Path::new("a")), (4, Path::new("d"))]
+        );
+        assert_eq!(history.jump_table(2), None);
+
+        // Undo skips the discarded generations
+        assert!(history.undo());
+        assert_eq!(history.generation(), 1);
+        assert!(history.redo());
+        assert_eq!(history.generation(), 4);
+    }
+
+    #[test]
+    fn limit_forgets_the_oldest_patches() {
+        let mut history = HotPatchHistory::default();
+        history.set_limit(2);
+        for name in ["a", "b", "c"] {
+            history.push(patch(name));
+        }
+        assert_eq!(
+            patches(&history),
+            [(2, Path::new("b")), (3, Path::new("c"))]
+        );
+        // The launch code can't be restored once a patch was forgotten
+        assert_eq!(history.oldest_generation(), 2);
+        assert!(history.undo());
+        assert!(!history.undo());
+        assert_eq!(history.generation(), 2);
+
+        // Lowering the limit moves the requested generation to the oldest one kept
+        history.set_limit(0);
+        assert_eq!(history.limit(), 1);
+        assert_eq!(patches(&history), [(3, Path::new("c"))]);
+        assert_eq!(history.generation(), 3);
+        assert!(!history.can_undo());
+    }
+}
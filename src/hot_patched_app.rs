@@ -1,47 +1,325 @@
 //! API for hot-patching new systems into your running app.
 //! See [`HotPatchedAppExt::with_hot_patch`] for the main API.
 
-use crate::__macros_internal::__ReloadPositions as ReloadPositions;
-use bevy_app::{
-    App, NonSendMarker, PostStartup, PostUpdate, PreStartup, PreUpdate, Startup, Update,
+use bevy_app::{App, First, Main, MainScheduleOrder, PostStartup, PreStartup, Startup};
+use bevy_ecs::{
+    component::{ComponentId, ComponentInfo},
+    entity::EntityHashMap,
+    hierarchy::Children,
+    prelude::*,
+    reflect::{AppTypeRegistry, ReflectComponent},
+    schedule::{
+        InternedScheduleLabel, NodeId, ScheduleBuildError, ScheduleBuildPass, ScheduleGraph,
+        ScheduleLabel, SystemNode,
+        common_conditions::run_once,
+        graph::{DiGraph, Direction},
+    },
+    system::ScheduleSystem,
+};
+use bevy_log::{debug, error, info_span, warn};
+use bevy_platform::collections::{HashMap, HashSet};
+use bevy_reflect::{Reflect, TypeRegistry};
+use core::panic::Location;
+use std::{
+    any::TypeId,
+    borrow::Cow,
+    sync::{LazyLock, Mutex},
+    time::Instant,
 };
-use bevy_derive::{Deref, DerefMut};
-use bevy_ecs::prelude::*;
-#[cfg(all(not(target_family = "wasm"), debug_assertions))]
-use bevy_ecs::system::{Commands, Res};
-use bevy_ecs_macros::ScheduleLabel;
-use bevy_log::{debug, error};
-
-use crate::HotPatched;
-
-/// Wrapper around [`App`] used by [`HotPatchedAppExt::with_hot_patch`], which allows you to add and remove systems at runtime.
-#[derive(Deref, DerefMut)]
-struct HotPatchedApp(send_wrapper::SendWrapper<App>);
 
-impl Default for HotPatchedApp {
-    fn default() -> Self {
-        HotPatchedApp(send_wrapper::SendWrapper::new(App::default()))
-    }
-}
+use crate::{HotPatched, catch_panics, diagnostics::HotPatchTimings, lock};
 
 /// The [`Startup`] schedule, but rerun on hot-reload.
 /// Only valid inside the context of [`HotPatchedAppExt::with_hot_patch`].
+///
+/// Entities spawned by this schedule are marked with [`SpawnedByHotRerun`]
+/// and despawned before it is rerun.
 #[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash, Default)]
 pub struct StartupRerunHotPatch;
 
-#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash, Default)]
-struct HotPatchUpdate;
+/// Marker for entities spawned by code that is rerun on hot patch.
+/// These entities are despawned, along with their children, before that code is rerun.
+/// This works no matter how the entities were spawned.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct SpawnedByHotRerun(pub HotRerunScope);
 
-#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash, Default)]
-struct HotPatchPostUpdate;
+/// Code that is rerun on hot patch and cleans up after its previous run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotRerunScope {
+    /// A schedule, e.g. [`StartupRerunHotPatch`].
+    Schedule(InternedScheduleLabel),
+    /// A system annotated with `#[hot(rerun_on_hot_patch = true, despawn_spawned = true)]`,
+    /// identified by its name.
+    System(&'static str),
+    /// The function passed to [`HotPatchedAppExt::with_hot_patch_named`], identified by the section's name.
+    /// Besides entities and resources, this includes observers and systems registered by it.
+    HotPatchedSection(&'static str),
+}
 
-#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash, Default)]
-struct HotPatchPreUpdate;
+/// Resources inserted by each [`HotRerunScope`] during its last run.
+#[derive(Resource, Default)]
+struct HotRerunResources(HashMap<HotRerunScope, Vec<ComponentId>>);
+
+/// Resource configuring how code that is rerun on hot patch cleans up after its previous run.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct HotRerunSettings {
+    /// Instead of despawning all entities spawned by the previous run, match the newly spawned
+    /// entities to their previous incarnations by [`HotRerunKey`] or, failing that, by [`Name`].
+    /// Matched entities are kept, preserving their [`Entity`] and the state they gained at runtime.
+    /// Only the components whose spawned value changed since the previous run are updated.
+    ///
+    /// Entities with components that are not reflected, e.g. entities with observers, are always respawned.
+    pub reconcile: bool,
+}
+
+/// Stable key used to match an entity to its previous incarnation when
+/// [`HotRerunSettings::reconcile`] is enabled. Takes precedence over [`Name`].
+#[derive(Component, Reflect, Debug, Clone, PartialEq, Eq, Hash)]
+#[reflect(Component)]
+pub struct HotRerunKey(pub Cow<'static, str>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ReconcileKey {
+    Key(Cow<'static, str>),
+    Name(String),
+}
+
+impl ReconcileKey {
+    fn of(entity: EntityRef) -> Option<Self> {
+        if let Some(key) = entity.get::<HotRerunKey>() {
+            return Some(Self::Key(key.0.clone()));
+        }
+        entity
+            .get::<Name>()
+            .map(|name| Self::Name(name.as_str().to_string()))
+    }
+}
+
+/// Reflected values of the components of every reconciled entity, as they were spawned.
+/// Used to tell components changed by the code apart from components changed at runtime.
+#[derive(Resource, Default)]
+struct HotRerunSnapshots(EntityHashMap<Vec<(ComponentId, Box<dyn Reflect>)>>);
+
+/// Event sent when a schedule fails to initialize after a hot patch,
+/// e.g. because the hot-patched systems conflict with other systems.
+/// The systems that were in the schedule before the hot patch are kept.
+///
+/// Bevy cannot undo the ordering of systems added to a schedule, so errors such as cycles
+/// are checked for before the hot-patched systems are added, and the section is not applied at all.
+#[derive(Event, Debug)]
+pub struct HotReloadScheduleError {
+    /// The schedule that failed to initialize.
+    pub schedule: InternedScheduleLabel,
+    /// The reason it failed to initialize.
+    pub error: ScheduleBuildError,
+}
+
+/// Applies the hot-patched sections. Runs right before [`First`], so that none of the schedules
+/// the sections add systems to is running, except for [`Main`].
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+struct HotPatchedSections;
+
+/// The [`StartupRerunHotPatch`] schedule of a hot-patched section.
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+struct HotPatchedSectionStartup(&'static str);
+
+/// Names of the sections added by [`HotPatchedAppExt::with_hot_patch`], by the location of the call.
+/// Every location is only turned into a name once, no matter how many apps it is called for.
+static CALLER_SECTIONS: LazyLock<Mutex<HashMap<&'static Location<'static>, &'static str>>> =
+    LazyLock::new(Default::default);
+
+/// Nodes added to the app's schedules by [`HotPatchedAppExt::with_hot_patch`].
+#[derive(Resource, Default)]
+struct HotPatchedNodes {
+    /// Names of the hot-patched sections.
+    sections: HashSet<&'static str>,
+    /// Nodes added by the current generation of each hot-patched section.
+    current: HashMap<&'static str, HashMap<InternedScheduleLabel, Vec<NodeId>>>,
+    /// Nodes of previous generations that have not been removed from their schedule yet.
+    removed: HashMap<InternedScheduleLabel, Vec<NodeId>>,
+    /// Nodes of previous generations that were removed from their schedule.
+    neutralized: HashMap<InternedScheduleLabel, Vec<NodeId>>,
+}
+
+/// Configured on a schedule to make it rebuild, which removes the systems of previous generations.
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+struct RemovedHotPatchedSystems;
+
+/// Replaces the systems of previous generations of the hot-patched section
+/// with a system that does nothing and never runs.
+/// Bevy cannot remove nodes from a schedule, so they are neutralized instead.
+/// The state of each removed system is moved to the system of the current generation replacing it.
+///
+/// Neutralized nodes no longer order other systems, but they stay in the schedule for good,
+/// and Bevy still checks the ordering they were added with for cycles before this pass runs.
+#[derive(Debug)]
+struct RemoveHotPatchedSystemsPass {
+    schedule: InternedScheduleLabel,
+}
+
+impl ScheduleBuildPass for RemoveHotPatchedSystemsPass {
+    type EdgeOptions = ();
+
+    fn add_dependency(&mut self, _from: NodeId, _to: NodeId, _options: Option<&Self::EdgeOptions>) {
+    }
+
+    fn collapse_set(
+        &mut self,
+        _set: NodeId,
+        _systems: &[NodeId],
+        _dependency_flattened: &DiGraph,
+    ) -> impl Iterator<Item = (NodeId, NodeId)> {
+        core::iter::empty()
+    }
+
+    fn build(
+        &mut self,
+        world: &mut World,
+        graph: &mut ScheduleGraph,
+        dependency_flattened: &mut DiGraph,
+    ) -> Result<(), ScheduleBuildError> {
+        let Some(mut nodes) = world.get_resource_mut::<HotPatchedNodes>() else {
+            return Ok(());
+        };
+        let removed = nodes.removed.remove(&self.schedule).unwrap_or_default();
+        let neutralized = nodes.neutralized.entry(self.schedule).or_default();
+        neutralized.extend(&removed);
+        let neutralized = neutralized.clone();
+        let mut current: Vec<_> = nodes
+            .current
+            .values()
+            .filter_map(|current| current.get(&self.schedule))
+            .flatten()
+            .copied()
+            .collect();
+        for node in removed {
+            let NodeId::System(index) = node else {
+                continue;
+            };
+            let replacement = graph.systems[index].get().and_then(|previous| {
+                // Systems of a generation that failed to apply are newer than the ones replacing them
+                current.iter().position(|&node| {
+                    node.index() > index
+                        && graph
+                            .get_system_at(node)
+                            .is_some_and(|system| is_same_system(&**previous, &**system))
+                })
+            });
+            let mut system: ScheduleSystem = Box::new(IntoSystem::into_system(removed_system));
+            system.initialize(world);
+            let mut previous =
+                core::mem::replace(&mut graph.systems[index], SystemNode::new(system));
+            // Carry the state of the system over to the system replacing it, if there is one
+            if let Some(replacement) = replacement {
+                let NodeId::System(replacement) = current.remove(replacement) else {
+                    unreachable!()
+                };
+                carry_over_state(&mut previous, &mut graph.systems[replacement]);
+            }
+            graph.system_conditions[index] = vec![Box::new(IntoSystem::into_system(never))];
+            for condition in &mut graph.system_conditions[index] {
+                condition.initialize(world);
+            }
+        }
+
+        // The flattened graph is built from scratch every time, so drop the edges on every build
+        for node in neutralized {
+            let edges: Vec<_> = dependency_flattened
+                .edges_directed(node, Direction::Incoming)
+                .chain(dependency_flattened.edges_directed(node, Direction::Outgoing))
+                .collect();
+            for (from, to) in edges {
+                dependency_flattened.remove_edge(from, to);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `system` is a new version of `previous`, i.e. whether they have the same name and type.
+fn is_same_system(
+    previous: &dyn System<In = (), Out = Result>,
+    system: &dyn System<In = (), Out = Result>,
+) -> bool {
+    previous.type_id() == system.type_id() && previous.name() == system.name()
+}
+
+/// Whether `previous` and `system` run the same code, i.e. whether they share a vtable.
+/// Only compares the vtables, as the data of both systems lives in different allocations.
+///
+/// Vtables are not guaranteed to be unique: the compiler may duplicate them, e.g. in the library
+/// of a hot patch, or merge identical ones. Merged vtables point to the same code, so systems
+/// that run different code are never taken for the same. A duplicated vtable only makes the
+/// system lose its state, as if its code had changed.
+fn runs_same_code(
+    previous: &dyn System<In = (), Out = Result>,
+    system: &dyn System<In = (), Out = Result>,
+) -> bool {
+    let previous = core::ptr::from_ref(previous);
+    core::ptr::eq(
+        previous,
+        core::ptr::from_ref(system).with_addr(previous.addr()),
+    )
+}
+
+/// Carries the state of the `previous` system, e.g. its [`Local`]s, [`EventReader`] cursors and last run tick,
+/// over to the `system` replacing it.
+///
+/// The state can only be kept as a whole, by keeping the previous system in place of the new one.
+/// That is only done if the code of the system did not change, which also rules out closures,
+/// as they may capture different values. Otherwise, only the last run tick is carried over.
+fn carry_over_state(previous: &mut SystemNode, system: &mut SystemNode) {
+    let (Some(previous_system), Some(new_system)) = (previous.get_mut(), system.get_mut()) else {
+        return;
+    };
+    if !new_system.name().contains("{{closure}}")
+        && runs_same_code(&**previous_system, &**new_system)
+    {
+        core::mem::swap(previous, system);
+    } else {
+        new_system.set_last_run(previous_system.get_last_run());
+    }
+}
+
+/// The function through which a hot-patched section is called.
+/// subsecond only looks up the patched version of zero-sized functions in the jump table:
+/// pointer-sized ones, e.g. `&mut F`, are taken for function pointers and always run the original code.
+fn section_entry<F: FnMut(&mut App)>() -> impl FnMut(&mut F, &mut App) + Copy {
+    fn call_section<F: FnMut(&mut App)>(func: &mut F, app: &mut App) {
+        func(app);
+    }
+    call_section::<F>
+}
+
+fn removed_system() -> Result {
+    Ok(())
+}
+
+fn never() -> bool {
+    false
+}
 
 /// Trait for [`App`] to add and remove systems at runtime.
 pub trait HotPatchedAppExt {
     /// Call this with plugins and systems and it will auto-add and remove systems in the `Update` schedule to your running app.
     ///
+    /// The systems are added directly to the app's schedules, so they can be ordered relative to and put into
+    /// system sets with systems outside of the hot-patched section. On every hot patch, the systems added
+    /// previously are removed and the section is applied again.
+    /// Systems that were added again without changes usually keep their state, e.g. their [`Local`]s and
+    /// [`EventReader`] cursors. Systems whose code changed, closures, and systems whose code can't be told
+    /// apart from the previous one's only keep their last run tick.
+    /// Observers, one-shot systems, entities and resources added by the previous application of the section
+    /// are removed as well, except for the resources of events, which stay registered.
+    ///
+    /// Bevy cannot remove systems from a schedule, so the systems of previous applications are replaced by
+    /// systems that never run and no longer order other systems. Each hot patch therefore grows the schedules
+    /// the section adds systems to a little, until the app is restarted. The ordering they were added with
+    /// is still checked for cycles, so reversing the order between a system set and a system outside of the section,
+    /// or ordering against a system of the section by its function, e.g. `b.after(a)`, is rejected with a
+    /// [`HotReloadScheduleError`] until the app is restarted. Order the section's systems with `chain` or
+    /// system sets instead. To check for such errors, the section is applied twice on every hot patch.
+    ///
     /// # Example
     ///
     /// ```ignore
@@ -53,14 +331,13 @@ pub trait HotPatchedAppExt {
     ///     .add_plugins(SimpleSubsecondPlugin::default())
     ///     .with_hot_patch(|app: &mut App| {
     ///         // Systems in the `StartupRerunHotPatch` schedule will be rerun on hot-reload.
-    ///         // They require `#[hot(hot_patch_signature = true)]`
+    ///         // Entities spawned by them are despawned before they rerun.
     ///         app.add_systems(StartupRerunHotPatch, setup);
     ///         // All other systems do not require `#[hot]`.
     ///         app.add_systems(Update, my_system);
-    ///         app.add_systems(PostUpdate, second_system);
+    ///         app.add_systems(PostUpdate, second_system.after(TransformSystem::TransformPropagate));
     ///     });
     ///
-    /// #[hot(hot_patch_signature = true)]
     /// fn setup(mut commands: Commands) {
     ///     commands.spawn(Camera2d::default());
     ///     commands.spawn(Text::new("Hello, world!"));
@@ -75,13 +352,176 @@ pub trait HotPatchedAppExt {
     /// }
     /// ```
     fn with_hot_patch(&mut self, func: impl FnMut(&mut App) + Send + Sync + 'static) -> &mut App;
+
+    /// Like [`HotPatchedAppExt::with_hot_patch`], but with a name identifying the hot-patched section.
+    /// Every section is hot-patched independently, so that e.g. each plugin of your game can own one.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// # use bevy::prelude::*;
+    /// # use bevy_simple_subsecond_system::prelude::*;
+    ///
+    /// fn ui_plugin(app: &mut App) {
+    ///     app.with_hot_patch_named("ui", |app: &mut App| {
+    ///         app.add_systems(StartupRerunHotPatch, spawn_ui);
+    ///     });
+    /// }
+    ///
+    /// fn player_plugin(app: &mut App) {
+    ///     app.with_hot_patch_named("player", |app: &mut App| {
+    ///         app.add_systems(StartupRerunHotPatch, spawn_player);
+    ///         app.add_systems(Update, move_player);
+    ///     });
+    /// }
+    /// # fn spawn_ui() {}
+    /// # fn spawn_player() {}
+    /// # fn move_player() {}
+    /// ```
+    fn with_hot_patch_named(
+        &mut self,
+        name: &'static str,
+        func: impl FnMut(&mut App) + Send + Sync + 'static,
+    ) -> &mut App;
+
+    /// Adds one or more [`HotPlugin`]s, each in its own hot-patched section named after [`HotPlugin::name`].
+    fn add_hot_plugins<M>(&mut self, plugins: impl HotPlugins<M>) -> &mut App;
+}
+
+/// A [`Plugin`](bevy_app::Plugin) whose [`build`](HotPlugin::build) runs inside a hot-patched section,
+/// see [`HotPatchedAppExt::with_hot_patch`]. Add it with [`HotPatchedAppExt::add_hot_plugins`].
+///
+/// On every hot patch, the systems, observers, entities and resources the plugin added previously are removed
+/// and `build` runs again, so they reflect the patched code. Events added with `add_event` are kept,
+/// along with the events that were not read yet.
+///
+/// Bevy cannot remove the configuration of system sets, so the run conditions and ordering added with
+/// `configure_sets` are added again on every hot patch, next to the previous ones. Changes to them therefore
+/// only take effect after a restart. Add run conditions to the systems instead to have them follow the patched code.
+///
+/// # Example
+///
+/// ```ignore
+/// # use bevy::prelude::*;
+/// # use bevy_simple_subsecond_system::prelude::*;
+///
+/// struct UiPlugin;
+///
+/// impl HotPlugin for UiPlugin {
+///     fn build(&self, app: &mut App) {
+///         app.add_systems(StartupRerunHotPatch, spawn_ui)
+///             .add_systems(Update, update_ui.run_if(resource_changed::<Score>));
+///     }
+/// }
+///
+/// App::new()
+///     .add_plugins((DefaultPlugins, SimpleSubsecondPlugin::default()))
+///     .add_hot_plugins(UiPlugin)
+///     .run();
+/// # #[derive(Resource)]
+/// # struct Score;
+/// # fn spawn_ui() {}
+/// # fn update_ui() {}
+/// ```
+pub trait HotPlugin: Send + Sync + 'static {
+    /// Configures the [`App`] to which this plugin is added. Rerun on every hot patch.
+    fn build(&self, app: &mut App);
+
+    /// Name of the plugin's hot-patched section, which must be unique.
+    fn name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+}
+
+/// Types that represent a set of [`HotPlugin`]s: a single one or a tuple of them.
+pub trait HotPlugins<Marker> {
+    /// Adds the plugins to the app.
+    fn add_to_app(self, app: &mut App);
+}
+
+#[doc(hidden)]
+pub struct HotPluginMarker;
+
+impl<P: HotPlugin> HotPlugins<HotPluginMarker> for P {
+    fn add_to_app(self, app: &mut App) {
+        app.with_hot_patch_named(self.name(), move |app: &mut App| {
+            // Call `build` through a hot function, so that changes to it are picked up
+            // even if this closure did not change
+            dioxus_devtools::subsecond::HotFn::current(P::build).call((&self, app));
+        });
+    }
+}
+
+macro_rules! impl_hot_plugins_tuple {
+    ($(($plugin:ident, $marker:ident)),*) => {
+        impl<$($plugin: HotPlugins<$marker>, $marker),*> HotPlugins<($($marker,)*)> for ($($plugin,)*) {
+            #[allow(non_snake_case)]
+            fn add_to_app(self, app: &mut App) {
+                let ($($plugin,)*) = self;
+                $($plugin.add_to_app(app);)*
+            }
+        }
+    };
 }
 
+impl_hot_plugins_tuple!((P0, M0));
+impl_hot_plugins_tuple!((P0, M0), (P1, M1));
+impl_hot_plugins_tuple!((P0, M0), (P1, M1), (P2, M2));
+impl_hot_plugins_tuple!((P0, M0), (P1, M1), (P2, M2), (P3, M3));
+impl_hot_plugins_tuple!((P0, M0), (P1, M1), (P2, M2), (P3, M3), (P4, M4));
+impl_hot_plugins_tuple!((P0, M0), (P1, M1), (P2, M2), (P3, M3), (P4, M4), (P5, M5));
+impl_hot_plugins_tuple!(
+    (P0, M0),
+    (P1, M1),
+    (P2, M2),
+    (P3, M3),
+    (P4, M4),
+    (P5, M5),
+    (P6, M6)
+);
+impl_hot_plugins_tuple!(
+    (P0, M0),
+    (P1, M1),
+    (P2, M2),
+    (P3, M3),
+    (P4, M4),
+    (P5, M5),
+    (P6, M6),
+    (P7, M7)
+);
+
 impl HotPatchedAppExt for App {
-    fn with_hot_patch(
+    #[track_caller]
+    fn with_hot_patch(&mut self, func: impl FnMut(&mut App) + Send + Sync + 'static) -> &mut App {
+        // Each call gets its own section, named after where it is made
+        let name = *lock(&CALLER_SECTIONS)
+            .entry(Location::caller())
+            .or_insert_with_key(|location| location.to_string().leak());
+        self.with_hot_patch_named(name, func)
+    }
+
+    fn add_hot_plugins<M>(&mut self, plugins: impl HotPlugins<M>) -> &mut App {
+        plugins.add_to_app(self);
+        self
+    }
+
+    fn with_hot_patch_named(
         &mut self,
+        name: &'static str,
         mut func: impl FnMut(&mut App) + Send + Sync + 'static,
     ) -> &mut App {
+        self.add_event::<HotReloadScheduleError>();
+        if !self
+            .world_mut()
+            .get_resource_or_init::<HotPatchedNodes>()
+            .sections
+            .insert(name)
+        {
+            warn!(
+                "The hot-patched section {name:?} already exists, so the sections will interfere"
+            );
+        }
+
         let mut app = App::new();
         app.init_schedule(Startup);
         app.init_schedule(PostStartup);
@@ -114,156 +554,805 @@ impl HotPatchedAppExt for App {
             self.get_schedule_mut(PostStartup).unwrap(),
         );
 
-        self.add_systems(PreUpdate, |world: &mut World| {
-            let _ = world.try_run_schedule(HotPatchPreUpdate);
-        });
-        self.add_systems(Update, |world: &mut World| {
-            let _ = world.try_run_schedule(HotPatchUpdate);
-        });
-        self.add_systems(PostUpdate, |world: &mut World| {
-            let _ = world.try_run_schedule(HotPatchPostUpdate);
-        });
-
-        self.add_systems(Startup, |world: &mut World| {
-            world.insert_resource(ReloadPositions::default());
-        });
-
-        let hot_patched_func = move |mut hot_patched_app: HotPatchedApp| -> HotPatchedApp {
-            func(&mut hot_patched_app.0);
-            hot_patched_app
-        };
-        let reloadable_section =
-            std::sync::Mutex::new(dioxus_devtools::subsecond::HotFn::current(hot_patched_func));
+        if !self
+            .world()
+            .resource::<Schedules>()
+            .contains(HotPatchedSections)
+        {
+            self.init_schedule(HotPatchedSections);
+            if let Some(mut order) = self.world_mut().get_resource_mut::<MainScheduleOrder>() {
+                order.insert_before(First, HotPatchedSections);
+            }
+        }
+        let mut first_run = true;
         self.add_systems(
-            PreUpdate,
-            move |_: Option<NonSend<NonSendMarker>>,
-                  mut ran_once: Local<bool>,
-                  mut schedules: ResMut<Schedules>,
-                  mut commands: Commands,
-                  hotreload_event: EventReader<HotPatched>| {
-                if hotreload_event.is_empty() {
-                    if *ran_once {
-                        return;
-                    }
-                    *ran_once = true;
-                }
-
-                let reload_app = reloadable_section
-                    .lock()
-                    .unwrap()
-                    .try_call((HotPatchedApp::default(),));
+            HotPatchedSections,
+            (move |world: &mut World| {
+                let _span = info_span!("hot_patch_section", section = name).entered();
+                let start = Instant::now();
+                let previous_len: HashMap<_, _> = world
+                    .resource::<Schedules>()
+                    .iter()
+                    .map(|(_, schedule)| (schedule.label(), schedule.graph().systems.len()))
+                    .collect();
+                world
+                    .resource_mut::<Schedules>()
+                    .remove(StartupRerunHotPatch);
 
-                let mut reload_app = match reload_app {
-                    Ok(reload_app) => reload_app,
-                    Err(e) => {
-                        error!("Failed to call hotpatch function: {e:?}");
-                        return;
+                let mut apply_section = |world: &mut World| {
+                    // Apply the section to the app's world, so that its systems are added straight into
+                    // the app's schedules and can be ordered relative to systems outside of it
+                    let mut app = App::empty();
+                    core::mem::swap(app.world_mut(), world);
+                    let result = catch_panics(name, || {
+                        // The `HotFn` is created for every call, as it can't outlive the borrow of the app
+                        dioxus_devtools::subsecond::HotFn::current(section_entry())
+                            .try_call((&mut func, &mut app))
+                    });
+                    core::mem::swap(app.world_mut(), world);
+                    match result {
+                        Some(Ok(())) => true,
+                        Some(Err(e)) => {
+                            error!("Failed to call hotpatch function: {e:?}");
+                            false
+                        }
+                        None => false,
                     }
                 };
 
-                let mut reload_schedules = reload_app
-                    .world_mut()
-                    .get_resource_mut::<Schedules>()
-                    .unwrap();
-
-                if let Some(mut reload_update) = reload_schedules.remove(Update) {
-                    schedules.remove(HotPatchUpdate);
-                    let hot_reload_update = schedules.entry(HotPatchUpdate);
-                    *hot_reload_update.graph_mut() = std::mem::take(reload_update.graph_mut());
-                    commands.run_system_cached(|world: &mut World| {
-                        world.schedule_scope(HotPatchUpdate, |world, hot_reload_update| {
-                            let result = hot_reload_update.initialize(world);
-                            if let Err(e) = result {
-                                error!("Failed to initialize hotpatch update: {e}");
-                            }
-                        });
+                // Bevy cannot undo the ordering of the systems added to a schedule, so on a hot patch
+                // the section is first applied to empty schedules, to check that the app's schedules
+                // can still be built with its systems. The second application cleans up after the first.
+                let checked = first_run
+                    || run_scoped(world, HotRerunScope::HotPatchedSection(name), |world| {
+                        let mut staged = Schedules::default();
+                        for (_, schedule) in world.resource::<Schedules>().iter() {
+                            staged.insert(Schedule::new(schedule.label()));
+                        }
+                        let schedules =
+                            core::mem::replace(&mut *world.resource_mut::<Schedules>(), staged);
+                        let applied = apply_section(world);
+                        let staged =
+                            core::mem::replace(&mut *world.resource_mut::<Schedules>(), schedules);
+                        applied && check_section(world, &staged)
                     });
-                };
 
-                if let Some(mut reload_post_update) = reload_schedules.remove(PostUpdate) {
-                    schedules.remove(HotPatchPostUpdate);
-                    let hot_reload_post_update = schedules.entry(HotPatchPostUpdate);
-                    *hot_reload_post_update.graph_mut() =
-                        std::mem::take(reload_post_update.graph_mut());
-                    commands.run_system_cached(|world: &mut World| {
-                        world.schedule_scope(
-                            HotPatchPostUpdate,
-                            |world, hot_reload_post_update| {
-                                let result = hot_reload_post_update.initialize(world);
-                                if let Err(e) = result {
-                                    error!("Failed to initialize hotpatch post-update: {e}");
-                                }
-                            },
+                // Observers, one-shot systems and resources added by the previous generation are removed
+                let applied = checked
+                    && run_scoped(world, HotRerunScope::HotPatchedSection(name), apply_section);
+                let mut schedules = world.resource_mut::<Schedules>();
+                // Running schedules are taken out of `Schedules`, so adding systems to them
+                // created a new schedule that would be overwritten once they are put back
+                for running in [Main.intern(), HotPatchedSections.intern()] {
+                    if schedules.remove(running).is_some() {
+                        warn!(
+                            "Hot-patched sections can't add systems to {running:?}, ignoring them"
                         );
+                    }
+                }
+                let mut current = HashMap::<InternedScheduleLabel, Vec<NodeId>>::default();
+                for (_, schedule) in schedules.iter_mut() {
+                    let label = schedule.label();
+                    if label == StartupRerunHotPatch.intern() {
+                        continue;
+                    }
+                    let previous_len = previous_len.get(&label).copied().unwrap_or_default();
+                    let nodes: Vec<_> = (previous_len..schedule.graph().systems.len())
+                        .map(NodeId::System)
+                        .collect();
+                    if !nodes.is_empty() {
+                        schedule.add_build_pass(RemoveHotPatchedSystemsPass { schedule: label });
+                        current.insert(label, nodes);
+                    }
+                }
+                // Keep the section's startup schedule apart from the ones of other sections
+                let startup = schedules
+                    .remove(StartupRerunHotPatch)
+                    .filter(|_| applied)
+                    .map(|mut schedule| {
+                        let mut section_startup = Schedule::new(HotPatchedSectionStartup(name));
+                        *section_startup.graph_mut() = core::mem::take(schedule.graph_mut());
+                        section_startup
                     });
-                };
 
-                if let Some(mut reload_pre_update) = reload_schedules.remove(PreUpdate) {
-                    schedules.remove(HotPatchPreUpdate);
-                    let hot_reload_pre_update = schedules.entry(HotPatchPreUpdate);
-                    *hot_reload_pre_update.graph_mut() =
-                        std::mem::take(reload_pre_update.graph_mut());
-                    commands.run_system_cached(|world: &mut World| {
-                        world.schedule_scope(HotPatchPreUpdate, |world, hot_reload_pre_update| {
-                            let result = hot_reload_pre_update.initialize(world);
-                            if let Err(e) = result {
-                                error!("Failed to initialize hotpatch pre-update: {e}");
-                            }
-                        });
-                    });
-                };
+                // Build the schedules with the systems of both the previous and the new generation,
+                // so that the previous systems can be kept if the new ones are invalid
+                // If the section failed to apply, its systems are removed right away
+                let mut failed = !applied;
+                let rebuild_span =
+                    info_span!("hot_patch_schedule_rebuild", section = name).entered();
+                for &label in current.keys().filter(|_| applied) {
+                    if !world.resource::<Schedules>().contains(label) {
+                        continue;
+                    }
+                    let result =
+                        world.schedule_scope(label, |world, schedule| schedule.initialize(world));
+                    if let Err(error) = result {
+                        report_schedule_error(world, label, error);
+                        failed = true;
+                    }
+                }
+                drop(rebuild_span);
 
-                if let Some(mut auto_reload_startup) = reload_schedules.remove(StartupRerunHotPatch)
-                {
-                    schedules.remove(StartupRerunHotPatch);
-                    let schedule: &mut Schedule = schedules.entry(StartupRerunHotPatch);
-                    *schedule.graph_mut() = std::mem::take(auto_reload_startup.graph_mut());
-                    commands.run_system_cached(|world: &mut World| {
-                        world.schedule_scope(StartupRerunHotPatch, |world, auto_reload_startup| {
-                            let result = auto_reload_startup.initialize(world);
-                            if let Err(e) = result {
-                                error!("Failed to initialize hotpatch auto_reload_startup: {e}");
-                            }
-                        });
-                    });
+                let mut nodes = world.resource_mut::<HotPatchedNodes>();
+                let previous = nodes.current.remove(name).unwrap_or_default();
+                let (kept, removed) = if failed {
+                    (previous, current)
+                } else {
+                    (current, previous)
+                };
+                nodes.current.insert(name, kept);
+                let mut rebuild = Vec::new();
+                for (label, removed) in removed {
+                    nodes.removed.entry(label).or_default().extend(removed);
+                    rebuild.push(label);
+                }
+                let mut schedules = world.resource_mut::<Schedules>();
+                for label in rebuild {
+                    if let Some(schedule) = schedules.get_mut(label) {
+                        schedule.configure_sets(RemovedHotPatchedSystems);
+                    }
+                }
 
-                    commands.run_system_cached(
-                        |mut commands: Commands,
-                         query: Query<Entity>,
-                         reload_positions: Res<ReloadPositions>,
-                         world: &World| {
-                            for e in query.iter() {
-                                let Some(location) = world
-                                    .entities()
-                                    .entity_get_spawned_or_despawned_by(e)
-                                    .into_option()
-                                else {
-                                    continue;
-                                };
-                                let Some(location) = location else { continue };
-                                for (file, line_start, line_end) in reload_positions.iter() {
-                                    if location.file() != *file {
-                                        continue;
-                                    }
-                                    if location.line() > *line_start && location.line() < *line_end
-                                    {
-                                        debug!("despawning an entity at: {location:?}");
-                                        commands.entity(e.entity()).despawn();
-                                    }
-                                }
-                            }
-                        },
-                    );
-                    commands.run_system_cached(|world: &mut World| {
-                        // we clear our reload positions every time so we can fill them up with new stuff.
-                        world.insert_resource(ReloadPositions::default());
-                        if let Err(e) = world.try_run_schedule(StartupRerunHotPatch) {
-                            error!("Failed to auto-reload startup: {e:?}");
+                if let Some(mut startup) = startup {
+                    let label = startup.label();
+                    match startup.initialize(world) {
+                        Ok(()) => {
+                            world.resource_mut::<Schedules>().insert(startup);
+                            rerun_schedule(world, label);
                         }
-                    })
+                        Err(error) => report_schedule_error(world, label, error),
+                    }
+                } else if applied {
+                    // The section no longer has a startup schedule, so clean up after the previous one
+                    let label = HotPatchedSectionStartup(name).intern();
+                    if world.resource_mut::<Schedules>().remove(label).is_some() {
+                        run_scoped(world, HotRerunScope::Schedule(label), |_| {});
+                    }
                 }
-            },
+
+                // The first run sets the section up instead of reacting to a hot patch
+                if !core::mem::take(&mut first_run) {
+                    if let Some(mut timings) = world.get_resource_mut::<HotPatchTimings>() {
+                        timings.record_schedule_rebuild(start.elapsed());
+                    }
+                }
+            })
+            .run_if(run_once.or(on_event::<HotPatched>)),
         );
         self
     }
 }
+
+fn report_schedule_error(
+    world: &mut World,
+    schedule: InternedScheduleLabel,
+    error: ScheduleBuildError,
+) {
+    error!(
+        "Failed to initialize {schedule:?} after hot patch, keeping the previous systems: {error}"
+    );
+    world.send_event(HotReloadScheduleError { schedule, error });
+}
+
+/// Checks that the app's schedules can still be built once the systems and sets of the `staged` schedules,
+/// which a section added to empty schedules, are added to them.
+/// Sends a [`HotReloadScheduleError`] for each schedule that could not be built.
+fn check_section(world: &mut World, staged: &Schedules) -> bool {
+    let nodes = world.resource::<HotPatchedNodes>();
+    let schedules = world.resource::<Schedules>();
+    let mut errors = Vec::new();
+    for (_, staged) in staged.iter() {
+        let label = staged.label();
+        let graph = staged.graph();
+        if label == StartupRerunHotPatch.intern()
+            || graph.systems.is_empty() && graph.system_sets().next().is_none()
+        {
+            continue;
+        }
+        // The systems of previous generations no longer order other systems once the schedule is built.
+        // The current generation of the section is only removed after the schedule was built with it.
+        let removed = [&nodes.removed, &nodes.neutralized]
+            .into_iter()
+            .filter_map(|nodes| nodes.get(&label))
+            .flatten()
+            .copied();
+        let mut structure = match schedules.get(label) {
+            Some(schedule) => ScheduleStructure::new(schedule),
+            None => ScheduleStructure::default(),
+        };
+        structure.add_staged(graph);
+        if let Err(error) = structure.check(removed.collect()) {
+            errors.push((label, error));
+        }
+    }
+    let checked = errors.is_empty();
+    for (label, error) in errors {
+        report_schedule_error(world, label, error);
+    }
+    checked
+}
+
+/// How the systems and sets of a schedule are nested and ordered, without the systems themselves.
+/// Unlike the [`ScheduleGraph`], it can be checked for errors before systems are added to the schedule.
+#[derive(Default)]
+struct ScheduleStructure {
+    hierarchy: DiGraph,
+    dependency: DiGraph,
+    names: HashMap<NodeId, String>,
+    set_ids: HashMap<Box<dyn SystemSet>, NodeId>,
+    /// The sets of all systems of a type, which can't be ordered against while they contain several systems.
+    system_type_sets: HashSet<NodeId>,
+    systems: usize,
+    sets: usize,
+}
+
+impl ScheduleStructure {
+    fn new(schedule: &Schedule) -> Self {
+        let graph = schedule.graph();
+        let mut structure = Self {
+            hierarchy: graph.hierarchy().graph().clone(),
+            dependency: graph.dependency().graph().clone(),
+            systems: graph.systems.len(),
+            ..Default::default()
+        };
+        // The systems of a schedule that was built are moved out of its graph
+        let systems = schedule.systems().into_iter().flatten();
+        for (id, system) in systems.chain(graph.systems().map(|(id, system, _)| (id, system))) {
+            structure.names.insert(id, system.name().into_owned());
+        }
+        for (id, set, _) in graph.system_sets() {
+            structure.add_set(id, set);
+        }
+        structure
+    }
+
+    fn add_set(&mut self, id: NodeId, set: &dyn SystemSet) {
+        self.names.insert(id, format!("{set:?}"));
+        self.set_ids.insert(set.dyn_clone(), id);
+        if set.system_type().is_some() {
+            self.system_type_sets.insert(id);
+        }
+        self.sets += 1;
+    }
+
+    /// Adds the systems and sets of `staged` to the structure, after the ones already in it.
+    /// Its sets are matched to the ones already in the structure, except for anonymous ones,
+    /// which are only equal to the sets of the graph that created them.
+    fn add_staged(&mut self, staged: &ScheduleGraph) {
+        let mut ids = HashMap::<NodeId, NodeId>::default();
+        for (id, system, _) in staged.systems() {
+            let new_id = NodeId::System(self.systems + id.index());
+            self.names.insert(new_id, system.name().into_owned());
+            ids.insert(id, new_id);
+        }
+        self.systems += staged.systems.len();
+        let sets = self.sets;
+        for (id, set, _) in staged.system_sets() {
+            let existing = self.set_ids.get(set).filter(|_| !set.is_anonymous());
+            let new_id = match existing {
+                Some(&existing) => existing,
+                None => {
+                    let new_id = NodeId::Set(sets + id.index());
+                    self.add_set(new_id, set);
+                    new_id
+                }
+            };
+            ids.insert(id, new_id);
+        }
+        for (graph, staged) in [
+            (&mut self.hierarchy, staged.hierarchy().graph()),
+            (&mut self.dependency, staged.dependency().graph()),
+        ] {
+            for node in staged.nodes() {
+                graph.add_node(ids[&node]);
+            }
+            for (from, to) in staged.all_edges() {
+                graph.add_edge(ids[&from], ids[&to]);
+            }
+        }
+    }
+
+    /// Runs the checks Bevy runs on a schedule before and after [`RemoveHotPatchedSystemsPass`],
+    /// which would fail every time the schedule is built from then on.
+    /// The edges of the `removed` systems are left out of the checks run after the pass.
+    fn check(&self, removed: HashSet<NodeId>) -> Result<(), ScheduleBuildError> {
+        if let Some(cycle) = find_cycle(&self.hierarchy) {
+            return Err(ScheduleBuildError::HierarchyCycle(
+                self.cycle_message("in_set", &cycle),
+            ));
+        }
+        if let Some(cycle) = find_cycle(&self.dependency) {
+            return Err(ScheduleBuildError::DependencyCycle(
+                self.cycle_message("before/after", &cycle),
+            ));
+        }
+
+        let contained = reachable(&self.hierarchy);
+        let ordered = reachable(&self.dependency);
+        let set_systems: HashMap<NodeId, HashSet<NodeId>> = contained
+            .iter()
+            .filter(|(id, _)| id.is_set())
+            .map(|(&id, nodes)| {
+                (
+                    id,
+                    nodes.iter().copied().filter(NodeId::is_system).collect(),
+                )
+            })
+            .collect();
+        for (&a, after) in &ordered {
+            for &b in after {
+                if contained[&a].contains(&b) || contained[&b].contains(&a) {
+                    return Err(ScheduleBuildError::CrossDependency(
+                        self.name(a),
+                        self.name(b),
+                    ));
+                }
+                if a.is_set() && b.is_set() && !set_systems[&a].is_disjoint(&set_systems[&b]) {
+                    return Err(ScheduleBuildError::SetsHaveOrderButIntersect(
+                        self.name(a),
+                        self.name(b),
+                    ));
+                }
+            }
+        }
+        for &set in &self.system_type_sets {
+            let ordered = self
+                .dependency
+                .edges_directed(set, Direction::Incoming)
+                .chain(self.dependency.edges_directed(set, Direction::Outgoing))
+                .next()
+                .is_some();
+            if ordered && set_systems[&set].len() > 1 {
+                return Err(ScheduleBuildError::SystemTypeSetAmbiguity(self.name(set)));
+            }
+        }
+
+        // Sets are replaced by their systems, like Bevy does before the build passes run
+        let mut flattened = self.dependency.clone();
+        for (&set, systems) in &set_systems {
+            let before: Vec<_> = flattened
+                .neighbors_directed(set, Direction::Incoming)
+                .collect();
+            let after: Vec<_> = flattened
+                .neighbors_directed(set, Direction::Outgoing)
+                .collect();
+            flattened.remove_node(set);
+            for &a in &before {
+                if systems.is_empty() {
+                    for &b in &after {
+                        flattened.add_edge(a, b);
+                    }
+                }
+                for &system in systems {
+                    flattened.add_edge(a, system);
+                }
+            }
+            for &b in &after {
+                for &system in systems {
+                    flattened.add_edge(system, b);
+                }
+            }
+        }
+        for node in removed {
+            if flattened.contains_node(node) {
+                flattened.remove_node(node);
+            }
+        }
+        match find_cycle(&flattened) {
+            Some(cycle) => Err(ScheduleBuildError::DependencyCycle(
+                self.cycle_message("before/after", &cycle),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn name(&self, id: NodeId) -> String {
+        self.names
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("{id:?}"))
+    }
+
+    fn cycle_message(&self, kind: &str, cycle: &[NodeId]) -> String {
+        let names: Vec<_> = cycle
+            .iter()
+            .chain(cycle.first())
+            .map(|&id| format!("`{}`", self.name(id)))
+            .collect();
+        format!("schedule has a {kind} cycle: {}", names.join(" -> "))
+    }
+}
+
+/// Finds a cycle in `graph`, returning its nodes in order.
+fn find_cycle(graph: &DiGraph) -> Option<Vec<NodeId>> {
+    fn visit(
+        graph: &DiGraph,
+        node: NodeId,
+        path: &mut Vec<NodeId>,
+        visited: &mut HashSet<NodeId>,
+    ) -> Option<Vec<NodeId>> {
+        path.push(node);
+        for next in graph.neighbors_directed(node, Direction::Outgoing) {
+            if let Some(start) = path.iter().position(|&node| node == next) {
+                return Some(path[start..].to_vec());
+            }
+            if visited.insert(next) {
+                if let Some(cycle) = visit(graph, next, path, visited) {
+                    return Some(cycle);
+                }
+            }
+        }
+        path.pop();
+        None
+    }
+
+    let mut visited = HashSet::default();
+    graph.nodes().find_map(|node| {
+        visited
+            .insert(node)
+            .then(|| visit(graph, node, &mut Vec::new(), &mut visited))
+            .flatten()
+    })
+}
+
+/// The nodes reachable from each node of the acyclic `graph`.
+fn reachable(graph: &DiGraph) -> HashMap<NodeId, HashSet<NodeId>> {
+    let mut reachable = HashMap::<NodeId, HashSet<NodeId>>::default();
+    for node in graph.nodes() {
+        let mut nodes = HashSet::default();
+        let mut stack: Vec<_> = graph
+            .neighbors_directed(node, Direction::Outgoing)
+            .collect();
+        while let Some(next) = stack.pop() {
+            if nodes.insert(next) {
+                stack.extend(graph.neighbors_directed(next, Direction::Outgoing));
+            }
+        }
+        reachable.insert(node, nodes);
+    }
+    reachable
+}
+
+/// Reruns the schedule, cleaning up after its previous run.
+fn rerun_schedule(world: &mut World, label: InternedScheduleLabel) {
+    run_scoped(world, HotRerunScope::Schedule(label), |world| {
+        if let Err(e) = world.try_run_schedule(label) {
+            error!("Failed to auto-reload startup: {e:?}");
+        }
+    });
+}
+
+/// Reruns the [`StartupRerunHotPatch`] schedules of the hot-patched sections, or of the one named `section`.
+/// Returns the names of the sections whose schedule was rerun.
+#[cfg(all(feature = "remote", not(target_family = "wasm"), debug_assertions))]
+pub(crate) fn rerun_startup(world: &mut World, section: Option<&str>) -> Vec<&'static str> {
+    let Some(nodes) = world.get_resource::<HotPatchedNodes>() else {
+        return Vec::new();
+    };
+    let mut sections: Vec<_> = nodes
+        .sections
+        .iter()
+        .copied()
+        .filter(|name| section.is_none_or(|section| section == *name))
+        .collect();
+    sections.sort_unstable();
+    sections.retain(|&name| {
+        let label = HotPatchedSectionStartup(name).intern();
+        if !world.resource::<Schedules>().contains(label) {
+            return false;
+        }
+        rerun_schedule(world, label);
+        true
+    });
+    sections
+}
+
+/// Despawns the entities spawned and removes the resources inserted by the previous run of `scope`,
+/// then calls `run` and keeps track of the entities and resources it adds.
+pub(crate) fn run_scoped<R>(
+    world: &mut World,
+    scope: HotRerunScope,
+    run: impl FnOnce(&mut World) -> R,
+) -> R {
+    let reconcile = world
+        .get_resource::<HotRerunSettings>()
+        .is_some_and(|settings| settings.reconcile);
+    let previously_spawned: Vec<_> = world
+        .query::<(Entity, &SpawnedByHotRerun)>()
+        .iter(world)
+        .filter(|(_, spawned_by)| spawned_by.0 == scope)
+        .map(|(entity, _)| entity)
+        .collect();
+
+    // Entities that can be matched to the entities spawned by this run
+    let mut candidates = HashMap::<ReconcileKey, Vec<Entity>>::default();
+    let mut despawned = Vec::new();
+    for entity in previously_spawned {
+        match ReconcileKey::of(world.entity(entity)).filter(|_| reconcile) {
+            Some(key) => candidates.entry(key).or_default().push(entity),
+            None => despawned.push(entity),
+        }
+    }
+    despawn(world, scope, despawned);
+
+    let previously_inserted = world
+        .get_resource_or_init::<HotRerunResources>()
+        .0
+        .remove(&scope)
+        .unwrap_or_default();
+    for resource in previously_inserted {
+        world.remove_resource_by_id(resource);
+    }
+
+    let existing_entities: HashSet<_> = world.iter_entities().map(|entity| entity.id()).collect();
+    let existing_resources: HashSet<_> =
+        world.iter_resources().map(|(info, _)| info.id()).collect();
+
+    let result = run(world);
+
+    let mut spawned: Vec<_> = world
+        .iter_entities()
+        .map(|entity| entity.id())
+        .filter(|entity| !existing_entities.contains(entity))
+        .collect();
+    if reconcile {
+        spawned = reconcile_entities(world, spawned, candidates);
+    }
+    world.insert_batch(
+        spawned
+            .into_iter()
+            .map(|entity| (entity, SpawnedByHotRerun(scope))),
+    );
+    let inserted = world
+        .iter_resources()
+        .filter(|(info, _)| !existing_resources.contains(&info.id()) && is_scoped(info))
+        .map(|(info, _)| info.id())
+        .collect();
+    world
+        .resource_mut::<HotRerunResources>()
+        .0
+        .insert(scope, inserted);
+
+    result
+}
+
+/// Whether the resource belongs to the scope it was inserted by, and is removed before it is rerun.
+/// The resources used to rerun scopes are inserted along the way, but belong to no scope.
+fn is_scoped(info: &ComponentInfo) -> bool {
+    let own = [
+        TypeId::of::<HotRerunSnapshots>(),
+        TypeId::of::<HotRerunResources>(),
+    ];
+    !is_events(info) && info.type_id().is_none_or(|type_id| !own.contains(&type_id))
+}
+
+/// Whether the resource holds the [`Events`] of an event. They are only registered with the
+/// [`EventRegistry`](bevy_ecs::event::EventRegistry), which updates them every frame, when they are
+/// first added, so they are kept instead of being removed along with the other resources of a scope.
+fn is_events(info: &ComponentInfo) -> bool {
+    #[derive(Event)]
+    struct AnyEvent;
+    let events = core::any::type_name::<Events<AnyEvent>>();
+    let (path, _) = events.split_once('<').unwrap_or((events, ""));
+    info.name()
+        .strip_prefix(path)
+        .is_some_and(|rest| rest.starts_with('<'))
+}
+
+fn despawn(world: &mut World, scope: HotRerunScope, entities: Vec<Entity>) {
+    if entities.is_empty() {
+        return;
+    }
+    debug!(
+        "Despawning {} entities spawned by the previous run of {scope:?}",
+        entities.len()
+    );
+    for entity in entities {
+        if let Some(mut snapshots) = world.get_resource_mut::<HotRerunSnapshots>() {
+            snapshots.0.remove(&entity);
+        }
+        // Children were already despawned along with their parent
+        if let Ok(entity) = world.get_entity_mut(entity) {
+            entity.despawn();
+        }
+    }
+}
+
+/// Matches the `spawned` entities to the `candidates` spawned by the previous run,
+/// updates the matched previous entities in place and despawns the new ones.
+/// Previous entities that were not matched are despawned.
+/// Returns the spawned entities that are kept.
+fn reconcile_entities(
+    world: &mut World,
+    spawned: Vec<Entity>,
+    mut candidates: HashMap<ReconcileKey, Vec<Entity>>,
+) -> Vec<Entity> {
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+
+    let mut matched = EntityHashMap::<Entity>::default();
+    for &entity in &spawned {
+        let Some(key) = ReconcileKey::of(world.entity(entity)) else {
+            continue;
+        };
+        if reflected_components(world, &registry, entity).is_none() {
+            continue;
+        }
+        let Some(previous) = candidates.get_mut(&key).and_then(|candidates| {
+            while !candidates.is_empty() {
+                let candidate = candidates.remove(0);
+                if world.get_entity(candidate).is_ok() {
+                    return Some(candidate);
+                }
+            }
+            None
+        }) else {
+            continue;
+        };
+        matched.insert(entity, previous);
+    }
+
+    let mut snapshots = world
+        .remove_resource::<HotRerunSnapshots>()
+        .unwrap_or_default();
+    let mut mapper = matched.clone();
+    for (&entity, &previous) in &matched {
+        let previous_snapshot = snapshots.0.remove(&previous).unwrap_or_default();
+        let mut snapshot = Vec::new();
+        for (component_id, reflect_component) in
+            reflected_components(world, &registry, entity).unwrap_or_default()
+        {
+            let Some(value) = reflect_component.reflect(world.entity(entity)) else {
+                continue;
+            };
+            let Ok(mut value) = value.reflect_clone() else {
+                continue;
+            };
+            // Point to the previous entities instead of the ones that are about to be despawned
+            reflect_component.map_entities(value.as_mut(), &mut mapper);
+            let unchanged = previous_snapshot
+                .iter()
+                .find(|(id, _)| *id == component_id)
+                .is_some_and(|(_, previous_value)| {
+                    previous_value.reflect_partial_eq(value.as_partial_reflect()) == Some(true)
+                });
+            // Keep the runtime state of components whose spawned value did not change
+            if !unchanged {
+                reflect_component.insert(
+                    &mut world.entity_mut(previous),
+                    value.as_partial_reflect(),
+                    &registry,
+                );
+            }
+            snapshot.push((component_id, value));
+        }
+        // Remove components that are no longer spawned
+        for (component_id, _) in &previous_snapshot {
+            if !snapshot.iter().any(|(id, _)| id == component_id) {
+                world.entity_mut(previous).remove_by_id(*component_id);
+            }
+        }
+        snapshots.0.insert(previous, snapshot);
+    }
+    if !matched.is_empty() {
+        debug!(
+            "Reconciled {} entities with their previous incarnation",
+            matched.len()
+        );
+    }
+
+    let kept: Vec<_> = spawned
+        .into_iter()
+        .filter(|entity| !matched.contains_key(entity))
+        .collect();
+    for &entity in &kept {
+        let Some(components) = reflected_components(world, &registry, entity) else {
+            continue;
+        };
+        let mut snapshot = Vec::new();
+        for (component_id, reflect_component) in components {
+            let Some(value) = reflect_component.reflect(world.entity(entity)) else {
+                continue;
+            };
+            let Ok(mut mapped) = value.reflect_clone() else {
+                continue;
+            };
+            // E.g. parent new children to the previous entity their parent was matched to
+            reflect_component.map_entities(mapped.as_mut(), &mut mapper);
+            if mapped.reflect_partial_eq(value.as_partial_reflect()) != Some(true) {
+                reflect_component.insert(
+                    &mut world.entity_mut(entity),
+                    mapped.as_partial_reflect(),
+                    &registry,
+                );
+            }
+            snapshot.push((component_id, mapped));
+        }
+        if ReconcileKey::of(world.entity(entity)).is_some() {
+            snapshots.0.insert(entity, snapshot);
+        }
+    }
+    world.insert_resource(snapshots);
+
+    for entity in matched.into_keys() {
+        if let Ok(entity) = world.get_entity_mut(entity) {
+            entity.despawn();
+        }
+    }
+    for entity in candidates.into_values().flatten() {
+        if let Some(mut snapshots) = world.get_resource_mut::<HotRerunSnapshots>() {
+            snapshots.0.remove(&entity);
+        }
+        if let Ok(entity) = world.get_entity_mut(entity) {
+            entity.despawn();
+        }
+    }
+
+    kept
+}
+
+/// The reflected components of an entity, or `None` if any of them is not reflected.
+/// [`Children`] are skipped, as they are kept in sync by [`ChildOf`].
+fn reflected_components(
+    world: &World,
+    registry: &TypeRegistry,
+    entity: Entity,
+) -> Option<Vec<(ComponentId, ReflectComponent)>> {
+    let entity = world.entity(entity);
+    let mut components = Vec::new();
+    for component_id in entity.archetype().components() {
+        let type_id = world.components().get_info(component_id)?.type_id()?;
+        if type_id == TypeId::of::<Children>() || type_id == TypeId::of::<SpawnedByHotRerun>() {
+            continue;
+        }
+        let reflect_component = registry.get_type_data::<ReflectComponent>(type_id)?;
+        components.push((component_id, reflect_component.clone()));
+    }
+    Some(components)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy_app::App;
+
+    use bevy_ecs::{prelude::*, system::ScheduleSystem};
+
+    use super::{runs_same_code, section_entry};
+
+    fn entry_size<F: FnMut(&mut App)>(_: &F) -> usize {
+        size_of_val(&section_entry::<F>())
+    }
+
+    #[test]
+    fn sections_are_called_through_a_zero_sized_function() {
+        // A section capturing a pointer is pointer-sized itself
+        let captured = Arc::new(0);
+        let section = move |_: &mut App| {
+            let _ = &captured;
+        };
+        assert_eq!(size_of_val(&section), size_of::<fn()>());
+        // Otherwise subsecond would not look up the patched section in the jump table
+        assert_eq!(entry_size(&section), 0);
+    }
+
+    fn first() -> Result {
+        Ok(())
+    }
+
+    fn second() -> Result {
+        Ok(())
+    }
+
+    fn boxed<M>(system: impl IntoSystem<(), Result, M>) -> ScheduleSystem {
+        Box::new(IntoSystem::into_system(system))
+    }
+
+    #[test]
+    fn systems_run_the_same_code_if_they_share_a_vtable() {
+        assert!(runs_same_code(&*boxed(first), &*boxed(first)));
+        assert!(!runs_same_code(&*boxed(first), &*boxed(second)));
+    }
+}
@@ -2,26 +2,59 @@
 #![allow(clippy::type_complexity)]
 #![doc = include_str!("../readme.md")]
 
+pub mod diagnostics;
+pub mod history;
 pub mod migration;
+pub mod patch_source;
+mod profiler;
+pub mod registry;
+#[cfg(all(feature = "remote", not(target_family = "wasm"), debug_assertions))]
+pub mod remote;
+
+#[cfg(all(
+    feature = "reload_assets",
+    not(target_family = "wasm"),
+    debug_assertions
+))]
+mod asset_reload;
 
-#[cfg(all(not(target_family = "wasm"), debug_assertions))]
-use __macros_internal::__HotPatchedSystems as HotPatchedSystems;
 use bevy_app::{App, Last, Plugin, PostStartup, PreUpdate};
 use bevy_ecs::prelude::*;
+use bevy_log::error;
 pub use bevy_simple_subsecond_system_macros::*;
 pub use dioxus_devtools;
+use dioxus_devtools::subsecond::HotFnPtr;
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+use dioxus_devtools::{DevserverMsg, subsecond::apply_patch};
 #[cfg(all(not(target_family = "wasm"), debug_assertions))]
-use dioxus_devtools::{subsecond::apply_patch, *};
+use patch_source::DevserverPatchSource;
+use patch_source::PatchSource;
+use std::{
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
 pub mod hot_patched_app;
 
 /// Everything you need to use hotpatching
 pub mod prelude {
     pub use super::{
-        HotPatched, SimpleSubsecondPlugin,
-        hot_patched_app::{HotPatchedAppExt as _, StartupRerunHotPatch},
+        DevserverShutdown, HotAssetsChanged, HotBuildFailed, HotBuildStarted, HotPatchFailed,
+        HotPatchFailedReason, HotPatched, HotSystemPanicked, HotSystemTiming,
+        SimpleSubsecondPlugin,
+        diagnostics::HotPatchDiagnosticsPlugin,
+        hot_patched_app::{
+            HotPatchedAppExt as _, HotPlugin, HotReloadScheduleError, HotRerunKey, HotRerunScope,
+            HotRerunSettings, SpawnedByHotRerun, StartupRerunHotPatch,
+        },
     };
+    pub use crate::history::HotPatchHistory;
     pub use crate::migration::*;
+    pub use crate::registry::{HotSystemInfo, HotSystemMode, HotSystemRegistry};
     pub use bevy_simple_subsecond_system_macros::*;
 }
 
@@ -37,9 +70,50 @@ pub mod prelude {
 ///     // rest of the setup
 ///     .run();
 /// ```
-#[derive(Debug, Default)]
+#[derive(Default)]
 #[non_exhaustive]
-pub struct SimpleSubsecondPlugin;
+pub struct SimpleSubsecondPlugin {
+    catch_panics: bool,
+    profile_systems: bool,
+    /// Taken when the plugin is built. `None` means the devserver.
+    patch_source: Mutex<Option<Box<dyn PatchSource>>>,
+}
+
+impl core::fmt::Debug for SimpleSubsecondPlugin {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SimpleSubsecondPlugin")
+            .field("catch_panics", &self.catch_panics)
+            .field("profile_systems", &self.profile_systems)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SimpleSubsecondPlugin {
+    /// Catch panics in hot-patched code instead of letting them crash the app.
+    /// This covers systems annotated with `#[hot]` that return `()` or `Result`,
+    /// and the functions passed to [`HotPatchedAppExt::with_hot_patch`](hot_patched_app::HotPatchedAppExt::with_hot_patch).
+    ///
+    /// A function that panicked is disabled until it is hot-patched, and a [`HotSystemPanicked`] event is sent.
+    pub fn catch_panics(mut self, catch_panics: bool) -> Self {
+        self.catch_panics = catch_panics;
+        self
+    }
+
+    /// Measure the execution time of systems annotated with `#[hot]`. When a system's code changes,
+    /// its average execution time before and after the hot patch is logged and sent as a [`HotSystemTiming`] event
+    /// once the new code ran 60 times.
+    pub fn profile_systems(mut self, profile_systems: bool) -> Self {
+        self.profile_systems = profile_systems;
+        self
+    }
+
+    /// Receive hot patches from `source` instead of the devserver started by `dx serve --hot-patch`.
+    /// See the [`patch_source`] module for the available sources.
+    pub fn with_patch_source(self, source: impl PatchSource) -> Self {
+        *lock(&self.patch_source) = Some(Box::new(source));
+        self
+    }
+}
 
 impl Plugin for SimpleSubsecondPlugin {
     fn build(&self, app: &mut App) {
@@ -62,43 +136,326 @@ impl Plugin for SimpleSubsecondPlugin {
         }
         #[cfg(all(not(target_family = "wasm"), debug_assertions))]
         {
-            let (sender, receiver) = crossbeam_channel::bounded::<HotPatched>(1);
-            connect(move |msg| {
-                if let DevserverMsg::HotReload(hot_reload_msg) = msg {
-                    if let Some(jumptable) = hot_reload_msg.jump_table {
-                        // SAFETY: This is not unsafe, but anything using the updated jump table is.
-                        // The table must be built carefully
-                        unsafe { apply_patch(jumptable).unwrap() };
-                        sender.send(HotPatched).unwrap();
-                    }
-                }
-            });
-
-            app.init_resource::<HotPatchedSystems>();
-
-            app.add_event::<HotPatched>().add_systems(
+            CATCH_PANICS.store(self.catch_panics, Ordering::Relaxed);
+            PROFILE_SYSTEMS.store(self.profile_systems, Ordering::Relaxed);
+            app.add_event::<HotSystemTiming>()
+                .add_systems(Last, profiler::send_timings);
+            app.add_event::<HotSystemPanicked>().add_systems(
                 Last,
-                move |mut events: EventWriter<HotPatched>| {
-                    if receiver.try_recv().is_ok() {
-                        events.write_default();
-                    }
+                |mut events: EventWriter<HotSystemPanicked>| {
+                    events.write_batch(core::mem::take(&mut *lock(&PANICKED)));
                 },
             );
 
-            app.init_resource::<migration::ComponentMigrations>();
+            let (sender, receiver) = crossbeam_channel::unbounded::<DevserverEvent>();
+            let source = lock(&self.patch_source)
+                .take()
+                .unwrap_or_else(|| Box::new(DevserverPatchSource));
+            let started_with = patch_source::ExecutableIdentity::current();
+            let activity = patch_source::PatchSourceActivity::default();
+            app.insert_resource(activity.clone());
+            source.connect(Box::new(move |msg| {
+                activity.0.fetch_add(1, Ordering::Relaxed);
+                let event = match msg {
+                    DevserverMsg::HotReload(hot_reload_msg) => {
+                        if !hot_reload_msg.assets.is_empty() {
+                            let _ = sender
+                                .send(DevserverEvent::AssetsChanged(hot_reload_msg.assets.clone()));
+                        }
+                        let Some(jumptable) = hot_reload_msg.jump_table.clone() else {
+                            return;
+                        };
+                        let _span = bevy_log::info_span!("hot_patch_apply").entered();
+                        let start = std::time::Instant::now();
+                        if let Err(reason) = patch_source::validate_patch(
+                            &hot_reload_msg,
+                            &jumptable,
+                            &patch_source::PatchTarget::running(started_with),
+                        ) {
+                            error!("Rejected hot patch: {reason}");
+                            let _ = sender.send(DevserverEvent::PatchFailed(reason));
+                            return;
+                        }
+                        // SAFETY: The jump table was built for this executable and process,
+                        // but anything using the updated jump table is still unsafe.
+                        match unsafe { apply_patch(jumptable.clone()) } {
+                            Ok(()) => DevserverEvent::HotPatched(jumptable, start.elapsed()),
+                            Err(err) => {
+                                let reason = HotPatchFailedReason::ApplyFailed(err.to_string());
+                                error!("Failed to apply hot patch: {reason}");
+                                DevserverEvent::PatchFailed(reason)
+                            }
+                        }
+                    }
+                    DevserverMsg::HotPatchStart | DevserverMsg::FullReloadStart => {
+                        DevserverEvent::BuildStarted
+                    }
+                    DevserverMsg::FullReloadFailed => DevserverEvent::BuildFailed,
+                    DevserverMsg::Shutdown => DevserverEvent::Shutdown,
+                    _ => return,
+                };
+                // The receiver is only dropped when the app exits
+                let _ = sender.send(event);
+            }));
+
+            app.init_resource::<hot_patched_app::HotRerunSettings>()
+                .register_type::<hot_patched_app::HotRerunKey>()
+                .init_resource::<history::HotPatchHistory>()
+                .init_resource::<registry::HotSystemRegistry>()
+                .add_systems(
+                    PreUpdate,
+                    (
+                        registry::add_new_systems,
+                        registry::dispatch.run_if(on_event::<HotPatched>),
+                    )
+                        .chain()
+                        .in_set(SimpleSubsecondSystemSet::UpdateFunctionPtrs),
+                );
+
+            app.add_event::<HotPatched>()
+                .add_event::<HotBuildStarted>()
+                .add_event::<HotBuildFailed>()
+                .add_event::<DevserverShutdown>()
+                .add_event::<HotAssetsChanged>()
+                .add_event::<HotPatchFailed>()
+                .add_systems(
+                    Last,
+                    (
+                        move |mut hot_patched: EventWriter<HotPatched>,
+                              mut build_started: EventWriter<HotBuildStarted>,
+                              mut build_failed: EventWriter<HotBuildFailed>,
+                              mut shutdown: EventWriter<DevserverShutdown>,
+                              mut assets_changed: EventWriter<HotAssetsChanged>,
+                              mut patch_failed: EventWriter<HotPatchFailed>,
+                              mut history: ResMut<history::HotPatchHistory>,
+                              mut timings: Option<ResMut<diagnostics::HotPatchTimings>>| {
+                            for event in receiver.try_iter() {
+                                match event {
+                                    DevserverEvent::HotPatched(jump_table, apply_time) => {
+                                        history.push(jump_table);
+                                        if let Some(timings) = &mut timings {
+                                            timings.record_apply(apply_time);
+                                        }
+                                        hot_patched.write_default();
+                                    }
+                                    DevserverEvent::BuildStarted => {
+                                        build_started.write_default();
+                                    }
+                                    DevserverEvent::BuildFailed => {
+                                        build_failed.write_default();
+                                    }
+                                    DevserverEvent::Shutdown => {
+                                        shutdown.write_default();
+                                    }
+                                    DevserverEvent::AssetsChanged(paths) => {
+                                        assets_changed.write(HotAssetsChanged { paths });
+                                    }
+                                    DevserverEvent::PatchFailed(reason) => {
+                                        patch_failed.write(HotPatchFailed { reason });
+                                    }
+                                }
+                            }
+                        },
+                        history::apply_requested_generation,
+                    )
+                        .chain(),
+                );
+
+            app.init_resource::<migration::ComponentMigrations>()
+                .init_resource::<migration::ComponentMigrationSettings>()
+                .add_event::<migration::ComponentMigrated>();
             app.add_systems(PostStartup, migration::register_migratable_components)
                 .add_systems(
                     PreUpdate,
-                    migration::migrate.in_set(SimpleSubsecondSystemSet::ComponentMigrations),
+                    (
+                        // Pick up types registered since the last hot patch, e.g. new monomorphizations of generic components
+                        migration::register_migratable_components.run_if(on_event::<HotPatched>),
+                        migration::migrate,
+                    )
+                        .chain()
+                        .in_set(SimpleSubsecondSystemSet::ComponentMigrations),
                 );
         }
     }
+
+    fn finish(&self, app: &mut App) {
+        // The `AssetPlugin` may be added after this plugin
+        #[cfg(all(
+            feature = "reload_assets",
+            not(target_family = "wasm"),
+            debug_assertions
+        ))]
+        asset_reload::plugin(app);
+        // The `RemotePlugin` may be added after this plugin
+        #[cfg(all(feature = "remote", not(target_family = "wasm"), debug_assertions))]
+        remote::plugin(app);
+        let _ = app;
+    }
 }
 
 /// Event sent when the hotpatch is applied.
 #[derive(Event, Default)]
 pub struct HotPatched;
 
+/// Event sent when the devserver starts rebuilding the app after a change.
+/// Followed by [`HotPatched`] or [`HotBuildFailed`].
+#[derive(Event, Debug, Default, Clone)]
+pub struct HotBuildStarted;
+
+/// Event sent when the devserver failed to rebuild the app.
+/// The devserver does not send the compiler diagnostics along, it prints them to its terminal.
+#[derive(Event, Debug, Default, Clone)]
+pub struct HotBuildFailed;
+
+/// Event sent when the devserver shuts down. No further hot patches will be applied.
+#[derive(Event, Debug, Default, Clone)]
+pub struct DevserverShutdown;
+
+/// Event sent when the devserver reports that assets changed.
+///
+/// With the `reload_assets` feature, the assets among them that are in the app's assets folder
+/// are reloaded by the [`AssetServer`](bevy_asset::AssetServer).
+#[derive(Event, Debug, Default, Clone)]
+pub struct HotAssetsChanged {
+    /// Paths of the changed assets, as reported by the devserver.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Messages from the [`PatchSource`], forwarded to the app as events.
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+enum DevserverEvent {
+    /// The jump table that was applied and how long it took.
+    HotPatched(dioxus_devtools::subsecond::JumpTable, core::time::Duration),
+    BuildStarted,
+    BuildFailed,
+    Shutdown,
+    AssetsChanged(Vec<PathBuf>),
+    PatchFailed(HotPatchFailedReason),
+}
+
+/// Event sent when a hot patch was received but not applied.
+/// The app keeps running the code it had before.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct HotPatchFailed {
+    /// Why the patch was not applied.
+    pub reason: HotPatchFailedReason,
+}
+
+/// Why a [`HotPatchFailed`] patch was not applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HotPatchFailedReason {
+    /// The patch was built for another build of the app, e.g. by a devserver still running from before the app was restarted.
+    WrongBuild {
+        /// The build the patch was built for.
+        patch: u64,
+        /// The build of the running app, read from the `DIOXUS_BUILD_ID` environment variable.
+        running: u64,
+    },
+    /// The patch was sent to another process running the same app.
+    WrongProcess {
+        /// The process the patch was built for.
+        patch: u32,
+        /// The running process.
+        running: u32,
+    },
+    /// The patch's base address does not match the running executable, so it was linked against a different binary.
+    BaseAddressMismatch {
+        /// Address of `main` in the binary the patch was linked against.
+        patch: u64,
+        /// Address of `main` in the running executable.
+        running: u64,
+    },
+    /// The executable was rebuilt since the app started, so the patch was likely linked against the new one.
+    /// Only checked for patches that do not say which build they were built for.
+    ExecutableChanged,
+    /// The library containing the patch does not exist.
+    MissingLibrary(PathBuf),
+    /// The library containing the patch could not be loaded.
+    ApplyFailed(String),
+}
+
+impl core::fmt::Display for HotPatchFailedReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::WrongBuild { patch, running } => write!(
+                f,
+                "the patch was built for build {patch}, but this is build {running}. Restart the devserver."
+            ),
+            Self::WrongProcess { patch, running } => write!(
+                f,
+                "the patch was built for process {patch}, but this is process {running}"
+            ),
+            Self::BaseAddressMismatch { patch, running } => write!(
+                f,
+                "the patch was linked against a different executable (main at {patch:#x}, running main at {running:#x}). Restart the devserver."
+            ),
+            Self::ExecutableChanged => write!(
+                f,
+                "the executable changed since the app started, so the patch was likely built against the new one. Restart the app."
+            ),
+            Self::MissingLibrary(path) => {
+                write!(f, "the patch library {} does not exist", path.display())
+            }
+            Self::ApplyFailed(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// Event sent when hot-patched code panicked and [`SimpleSubsecondPlugin::catch_panics`] is enabled.
+/// The code that panicked is disabled until it is hot-patched.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct HotSystemPanicked {
+    /// Name of the system or hot-patched section that panicked.
+    pub name: &'static str,
+    /// The panic message.
+    pub message: String,
+}
+
+/// Event sent when the code of a `#[hot]` system changed and [`SimpleSubsecondPlugin::profile_systems`] is enabled,
+/// once the new code ran often enough to compare its execution time to the previous code.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct HotSystemTiming {
+    /// Name of the system.
+    pub name: &'static str,
+    /// Average execution time before the hot patch.
+    pub before: core::time::Duration,
+    /// Average execution time after the hot patch.
+    pub after: core::time::Duration,
+}
+
+static CATCH_PANICS: AtomicBool = AtomicBool::new(false);
+static PROFILE_SYSTEMS: AtomicBool = AtomicBool::new(false);
+/// Panics caught since the last frame.
+static PANICKED: Mutex<Vec<HotSystemPanicked>> = Mutex::new(Vec::new());
+/// Functions that panicked, along with the pointer to the version that panicked.
+static DISABLED: Mutex<Vec<(&'static str, HotFnPtr)>> = Mutex::new(Vec::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Calls `call`, catching panics if [`SimpleSubsecondPlugin::catch_panics`] is enabled.
+/// Returns `None` if it panicked.
+pub(crate) fn catch_panics<R>(name: &'static str, call: impl FnOnce() -> R) -> Option<R> {
+    if !CATCH_PANICS.load(Ordering::Relaxed) {
+        return Some(call());
+    }
+    match std::panic::catch_unwind(AssertUnwindSafe(call)) {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Box<dyn Any>".to_string());
+            error!("{name} panicked and is disabled until it is hot-patched: {message}");
+            lock(&PANICKED).push(HotSystemPanicked { name, message });
+            None
+        }
+    }
+}
+
 /// System set in which components are migrated after a hot patch.
 /// Belongs to the [`PreUpdate`] schedule.
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
@@ -112,29 +469,117 @@ pub enum SimpleSubsecondSystemSet {
 
 #[doc(hidden)]
 pub mod __macros_internal {
-    pub use bevy_app::PreUpdate;
-    use bevy_derive::{Deref, DerefMut};
     pub use bevy_ecs::{
-        schedule::Schedules,
-        system::{IntoSystem, SystemId, SystemState},
+        component::Component,
+        system::{SystemId, SystemState},
         world::World,
     };
-    pub use bevy_ecs_macros::Resource;
-    pub use bevy_log::debug;
-    use bevy_platform::collections::{HashMap, HashSet};
-    use dioxus_devtools::subsecond::HotFnPtr;
-    use std::any::TypeId;
+    pub use bevy_reflect::{FromReflect, PartialReflect};
+    use dioxus_devtools::subsecond::{HotFn, HotFnPtr, HotFunction};
+    use std::sync::atomic::Ordering;
 
-    #[derive(Resource, Default)]
-    pub struct __HotPatchedSystems(pub HashMap<TypeId, __HotPatchedSystem>);
+    use crate::hot_patched_app::{HotRerunScope, run_scoped};
+    pub use crate::registry::HotSystemMode;
+    use crate::{CATCH_PANICS, DISABLED, PROFILE_SYSTEMS, catch_panics, lock};
 
-    #[doc(hidden)]
-    pub struct __HotPatchedSystem {
-        pub current_ptr: HotFnPtr,
-        pub last_ptr: HotFnPtr,
+    /// Calls a `#[hot]` system, catching panics if [`SimpleSubsecondPlugin::catch_panics`](crate::SimpleSubsecondPlugin::catch_panics)
+    /// is enabled. Returns `None` if the system panicked or is disabled because it panicked before.
+    pub fn __call_guarded<A, M, F: HotFunction<A, M>>(
+        system: &'static str,
+        hot_fn: &mut HotFn<A, M, F>,
+        args: A,
+    ) -> Option<F::Return> {
+        if !CATCH_PANICS.load(Ordering::Relaxed) {
+            return Some(__call(system, hot_fn, args));
+        }
+        let ptr = hot_fn.ptr_address();
+        if lock(&DISABLED).contains(&(system, ptr)) {
+            return None;
+        }
+        let result = catch_panics(system, || __call(system, hot_fn, args));
+        if result.is_none() {
+            lock(&DISABLED).push((system, ptr));
+        }
+        result
     }
 
-    #[doc(hidden)]
-    #[derive(Deref, DerefMut, Resource, Default, Debug)]
-    pub struct __ReloadPositions(pub HashSet<(&'static str, u32, u32)>);
+    /// Adds a `#[hot]` system to the [`HotSystemRegistry`](crate::registry::HotSystemRegistry) the first time it runs.
+    pub fn __register_hot_system(
+        system: &'static str,
+        file: &'static str,
+        lines: (u32, u32),
+        mode: HotSystemMode,
+        get_ptr: fn() -> HotFnPtr,
+        rerun: Option<fn(&mut World)>,
+    ) {
+        crate::registry::register(system, file, lines, mode, get_ptr, rerun);
+    }
+
+    /// Calls a `#[hot]` system, measuring its execution time if
+    /// [`SimpleSubsecondPlugin::profile_systems`](crate::SimpleSubsecondPlugin::profile_systems) is enabled.
+    pub fn __call<A, M, F: HotFunction<A, M>>(
+        system: &'static str,
+        hot_fn: &mut HotFn<A, M, F>,
+        args: A,
+    ) -> F::Return {
+        if !PROFILE_SYSTEMS.load(Ordering::Relaxed) {
+            return hot_fn.call(args);
+        }
+        let ptr = hot_fn.ptr_address();
+        crate::profiler::profile(system, ptr, || hot_fn.call(args))
+    }
+
+    /// Runs a `#[hot(rerun_on_hot_patch = true, despawn_spawned = true)]` system,
+    /// cleaning up after its previous run.
+    pub fn __run_scoped<R>(
+        world: &mut World,
+        system: &'static str,
+        run: impl FnOnce(&mut World) -> R,
+    ) -> R {
+        run_scoped(world, HotRerunScope::System(system), run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::sync::atomic::Ordering;
+
+    use dioxus_devtools::subsecond::HotFn;
+
+    use crate::{
+        __macros_internal::__call_guarded, CATCH_PANICS, HotSystemPanicked, PANICKED, lock,
+    };
+
+    fn broken(calls: &Cell<u32>) {
+        calls.set(calls.get() + 1);
+        panic!("broken");
+    }
+
+    fn fixed(calls: &Cell<u32>) {
+        calls.set(calls.get() + 1);
+    }
+
+    #[test]
+    fn panicking_systems_run_again_once_patched() {
+        CATCH_PANICS.store(true, Ordering::Relaxed);
+        let calls = Cell::new(0);
+
+        let mut hot_fn = HotFn::current(broken as fn(&Cell<u32>));
+        assert_eq!(__call_guarded("system", &mut hot_fn, (&calls,)), None);
+        assert_eq!(__call_guarded("system", &mut hot_fn, (&calls,)), None);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(
+            core::mem::take(&mut *lock(&PANICKED)),
+            [HotSystemPanicked {
+                name: "system",
+                message: "broken".to_string(),
+            }]
+        );
+
+        // A hot patch changes the pointer the system is called through
+        let mut hot_fn = HotFn::current(fixed as fn(&Cell<u32>));
+        assert_eq!(__call_guarded("system", &mut hot_fn, (&calls,)), Some(()));
+        assert_eq!(calls.get(), 2);
+    }
 }
@@ -1,6 +1,6 @@
 //! Enabled component migration when hot patching happens.
 //!
-//! Implement [`Reflect`], [`HotPatchMigrate`], [`Default`] and [`Component`]
+//! Implement [`Reflect`], [`HotPatchMigrate`], [`Component`] and optionally [`Default`]
 //! and reflect them for the component you want to migrate.
 //! ```
 //! # use bevy_simple_subsecond_system::prelude::*;
@@ -31,29 +31,46 @@
 //!     // ...register other components
 //! }
 //! ```
+//!
+//! Generic components are migrated for every monomorphization registered this way,
+//! e.g. `registry.register::<Health<Player>>()`.
+//!
+//! Every migration sends a [`ComponentMigrated`] event listing the migrated entities
+//! and which fields were added, removed or could not be carried over.
+//! To see what a migration would do before it happens, enable
+//! [`ComponentMigrationSettings::dry_run`].
 
 use bevy_derive::{Deref, DerefMut};
 use bevy_ecs::{
     component::Component,
     entity::Entity,
+    event::Event,
     query::QueryBuilder,
     reflect::{AppTypeRegistry, ReflectComponent},
     resource::Resource,
-    system::{Res, ResMut},
+    system::{Local, Res, ResMut},
     world::World,
 };
-use bevy_log::warn;
-use bevy_platform::sync::Arc;
-use bevy_reflect::{FromType, Reflect, std_traits::ReflectDefault};
+use bevy_log::{debug, info_span, warn};
+use bevy_platform::{collections::HashSet, sync::Arc, time::Instant};
+use bevy_reflect::{
+    ApplyError, DynamicArray, DynamicEnum, DynamicList, DynamicMap, DynamicSet, DynamicStruct,
+    DynamicTuple, DynamicTupleStruct, DynamicVariant, FromReflect, FromType, Map, PartialReflect,
+    Reflect, ReflectCloneError, ReflectFromReflect, ReflectRef, Set, TypeInfo, TypeRegistry,
+    VariantType, std_traits::ReflectDefault,
+};
 use bevy_utils::TypeIdMap;
-use core::any::{Any, TypeId};
+use core::{
+    any::{Any, TypeId},
+    fmt::Display,
+    time::Duration,
+};
 
 /// Enables migration for your components. Should be derived and
 /// not implemented manually.
 ///
-/// Requires that the type also implementes `Any`, `Reflect`,
-/// `Component` and `Default`. Last two (and `HotPatchMigrate`)
-/// should be reflected.
+/// Requires that the type also implementes `Any`, `Reflect` and
+/// `Component`. `Component` (and `HotPatchMigrate`) should be reflected.
 ///
 /// ```
 /// # use bevy_simple_subsecond_system::prelude::*;
@@ -66,25 +83,143 @@ use core::any::{Any, TypeId};
 /// ```
 ///
 /// Supports renaming the struct and field addition/removal.
-pub trait HotPatchMigrate: Any + Component + Reflect + Default {
+/// Components required by the new definition are inserted when migrating.
+/// See [`Migrating`] for telling migrations apart from gameplay code in hooks and observers.
+///
+/// Fields that cannot be taken from the previous definition, e.g. because they were just added,
+/// are filled with the value given by `#[hot_migrate(default = ...)]`, or else with the value
+/// in the reflected `Default` of the component. This means `Default` is not needed if every
+/// such field has a `#[hot_migrate(default = ...)]`:
+///
+/// ```
+/// # use bevy_simple_subsecond_system::prelude::*;
+/// # use bevy::prelude::*;
+/// #[derive(Debug, Reflect, Component, HotPatchMigrate)]
+/// #[reflect(Component, HotPatchMigrate)]
+/// struct Follow {
+///     target: Entity,
+///     #[hot_migrate(default = 5.0)]
+///     distance: f32,
+/// }
+/// ```
+pub trait HotPatchMigrate: Any + Component + FromReflect {
     /// The default derive implementation is a hot patchable function
     /// which means that this will always return the current type id
     /// (if a struct is changed it will start returning a new type id).
     fn current_type_id() -> TypeId;
+
+    /// Value of the given field to use when it cannot be taken from the previous definition.
+    /// Tuple struct fields are named by their index.
+    /// The derive implementation returns the values set with `#[hot_migrate(default = ...)]`.
+    fn default_field(field: &str) -> Option<Box<dyn PartialReflect>> {
+        let _ = field;
+        None
+    }
 }
 
 /// [`bevy_reflect::TypeData`] corresponding to the [`HotPatchMigrate`] trait. It contains the
-/// [`HotPatchMigrate::current_type_id`] method. You don't need to use this
-/// directly for hot patching or struct migration.
+/// [`HotPatchMigrate::current_type_id`] method and typed helpers used to migrate many entities
+/// at once. You don't need to use this directly for hot patching or struct migration.
 #[derive(Clone)]
-pub struct ReflectHotPatchMigrate(pub Arc<dyn Fn() -> TypeId + Sync + Send + 'static>);
+pub struct ReflectHotPatchMigrate {
+    current_type_id: Arc<dyn Fn() -> TypeId + Sync + Send + 'static>,
+    default_field: fn(&str) -> Option<Box<dyn PartialReflect>>,
+    from_reflect: fn(&dyn PartialReflect) -> Option<Box<dyn Reflect>>,
+    insert_batch: fn(&mut World, Vec<(Entity, Box<dyn Reflect>)>),
+    remove_batch: fn(&mut World, &[Entity]),
+}
+
+impl ReflectHotPatchMigrate {
+    /// Calls [`HotPatchMigrate::current_type_id`] for the reflected type.
+    pub fn current_type_id(&self) -> TypeId {
+        (self.current_type_id)()
+    }
+}
 
 impl<T: HotPatchMigrate> FromType<T> for ReflectHotPatchMigrate {
     fn from_type() -> Self {
-        Self(Arc::new(T::current_type_id))
+        Self {
+            current_type_id: Arc::new(T::current_type_id),
+            default_field: T::default_field,
+            from_reflect: |value| T::from_reflect(value).map(|value| Box::new(value) as _),
+            insert_batch: |world, values| {
+                let batch: Vec<_> = values
+                    .into_iter()
+                    .filter_map(|(entity, value)| {
+                        Some((entity, (*value.downcast::<T>().ok()?, Migrating)))
+                    })
+                    .collect();
+                if let Err(err) = world.try_insert_batch(batch) {
+                    warn!("Failed to migrate some entities: {err}");
+                }
+            },
+            remove_batch: |world, entities| {
+                // Bevy has no batched removal. Each entity is still moved on its own, like with
+                // `try_insert_batch`, and the archetype it moves to is cached after the first one.
+                for entity in entities {
+                    if let Ok(mut entity_mut) = world.get_entity_mut(*entity) {
+                        entity_mut.remove::<(T, Migrating)>();
+                    }
+                }
+            },
+        }
     }
 }
 
+/// Marker component present on an entity while one of its components is being migrated.
+///
+/// Migration inserts the new definition of a component and removes the old one, which
+/// triggers the same hooks and observers as gameplay code doing so would.
+/// Check for this component to ignore lifecycle events caused by a migration:
+///
+/// ```
+/// # use bevy_simple_subsecond_system::prelude::*;
+/// # use bevy::prelude::*;
+/// # #[derive(Component)]
+/// # struct Enemy;
+/// fn on_enemy_removed(trigger: Trigger<OnRemove, Enemy>, migrating: Query<(), With<Migrating>>) {
+///     if migrating.contains(trigger.target()) {
+///         return;
+///     }
+///     // ...
+/// }
+/// ```
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Migrating;
+
+/// Event sent for every component type that was migrated after a hot patch.
+/// If [`ComponentMigrationSettings::dry_run`] is enabled, it is sent instead for
+/// every migration that *would* happen, without touching the world.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ComponentMigrated {
+    /// Type path of the component before the hot patch.
+    pub from_name: String,
+    /// Type path of the component after the hot patch.
+    pub to_name: String,
+    /// Entities that contained the component.
+    pub entities: Vec<Entity>,
+    /// Fields that only exist in the new definition. They contain default values.
+    pub fields_added: Vec<String>,
+    /// Fields that only exist in the old definition. Their data is lost.
+    pub fields_removed: Vec<String>,
+    /// Fields that exist in both definitions, but whose data could not be carried over,
+    /// e.g. because their type changed. They contain default values.
+    pub fields_failed: Vec<String>,
+    /// Whether this is only a report of a pending migration.
+    pub dry_run: bool,
+    /// How long the migration took. Zero for dry runs.
+    pub duration: Duration,
+}
+
+/// Resource configuring how components are migrated after a hot patch.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct ComponentMigrationSettings {
+    /// When `true`, migrations are not applied. Instead, a [`ComponentMigrated`] event
+    /// describing the changes between the old and new definition is sent once per migration.
+    /// Pending migrations are applied as soon as this is set back to `false`.
+    pub dry_run: bool,
+}
+
 #[derive(Resource, Default, Deref, DerefMut)]
 pub(crate) struct ComponentMigrations(TypeIdMap<Arc<dyn Fn() -> TypeId + Sync + Send + 'static>>);
 
@@ -93,17 +228,24 @@ pub(crate) fn register_migratable_components(
     registry: Res<AppTypeRegistry>,
 ) {
     for registration in registry.read().iter() {
-        let Some(current_type_id) = registration.data::<ReflectHotPatchMigrate>() else {
+        let Some(reflect_migrate) = registration.data::<ReflectHotPatchMigrate>() else {
             continue;
         };
 
         migrations
             .entry(registration.type_id())
-            .or_insert_with(|| current_type_id.clone().0);
+            .or_insert_with(|| reflect_migrate.current_type_id.clone());
     }
 }
 
-pub(crate) fn migrate(world: &mut World) {
+pub(crate) fn migrate(
+    world: &mut World,
+    mut reported: Local<HashSet<(TypeId, TypeId)>>,
+    mut attempted: Local<HashSet<(TypeId, TypeId)>>,
+) {
+    let dry_run = world
+        .get_resource::<ComponentMigrationSettings>()
+        .is_some_and(|settings| settings.dry_run);
     let migrations = world.resource::<ComponentMigrations>();
     let changed: Vec<_> = migrations
         .iter()
@@ -111,8 +253,29 @@ pub(crate) fn migrate(world: &mut World) {
         .map(|(prev, current)| (*prev, current.clone()))
         .collect();
 
+    if dry_run {
+        for (prev, current) in &changed {
+            // Only report each pending migration once instead of every frame
+            if !reported.insert((*prev, current())) {
+                continue;
+            }
+            if let Some(report) = dry_run_component(world, *prev, current()) {
+                world.send_event(report);
+            }
+        }
+        return;
+    }
+    reported.clear();
+
     for (prev, current) in &changed {
-        migrate_component(world, *prev, current());
+        // Entities that failed to migrate keep the previous definition. They are only
+        // migrated again once it is hot-patched, instead of failing again every frame.
+        if !attempted.insert((*prev, current())) {
+            continue;
+        }
+        if let Some(report) = migrate_component(world, *prev, current()) {
+            world.send_event(report);
+        }
     }
 
     // Track hot patches to the new struct
@@ -120,61 +283,396 @@ pub(crate) fn migrate(world: &mut World) {
     migrations.extend(changed.into_iter().map(|(_, current)| (current(), current)));
 }
 
-fn migrate_component(world: &mut World, prev: TypeId, to: TypeId) {
-    world.resource_scope::<AppTypeRegistry, ()>(|world, registry| {
-        let registry = registry.read();
-        let Some(from_component_id) = world.components().get_id(prev) else {
-            // If there is no ComponentId, it doesn't exist in bevy's storages so there is nothing to migrate
-            return;
-        };
+/// Difference between the fields of two reflected types.
+#[derive(Default)]
+struct FieldDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<String>,
+}
+
+impl FieldDiff {
+    fn new(from: Option<&TypeInfo>, to: Option<&TypeInfo>) -> Self {
+        let from = from.map(fields).unwrap_or_default();
+        let to = to.map(fields).unwrap_or_default();
+        let mut diff = Self::default();
+        for (name, type_path) in &to {
+            match from.iter().find(|(prev_name, _)| prev_name == name) {
+                None => diff.added.push(name.clone()),
+                Some((_, prev_type_path)) if prev_type_path != type_path => {
+                    diff.changed.push(name.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, _) in &from {
+            if !to.iter().any(|(new_name, _)| new_name == name) {
+                diff.removed.push(name.clone());
+            }
+        }
+        diff
+    }
+}
 
-        let name = world
-            .components()
-            .get_name(from_component_id)
-            .unwrap_or_else(|| "Unknown".into());
+/// Names and type paths of the fields of a struct or tuple struct.
+fn fields(type_info: &TypeInfo) -> Vec<(String, &'static str)> {
+    match type_info {
+        TypeInfo::Struct(info) => info
+            .iter()
+            .map(|field| (field.name().to_string(), field.type_path()))
+            .collect(),
+        TypeInfo::TupleStruct(info) => info
+            .iter()
+            .map(|field| (field.index().to_string(), field.type_path()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Entities containing the component with the given [`TypeId`].
+fn entities_with(world: &mut World, type_id: TypeId) -> Vec<Entity> {
+    let Some(component_id) = world.components().get_id(type_id) else {
+        // If there is no ComponentId, it doesn't exist in bevy's storages so there is nothing to migrate
+        return Vec::new();
+    };
+    let mut builder = QueryBuilder::<Entity>::new(world);
+    builder.with_id(component_id);
+    let mut query = builder.build();
+    query.iter(world).collect()
+}
+
+fn type_name(world: &World, type_id: TypeId) -> String {
+    let registry = world.resource::<AppTypeRegistry>().read();
+    registry
+        .get_type_info(type_id)
+        .map(|info| info.type_path().to_string())
+        .or_else(|| {
+            let component_id = world.components().get_id(type_id)?;
+            world
+                .components()
+                .get_name(component_id)
+                .map(|name| name.to_string())
+        })
+        .unwrap_or_else(|| "Unknown".into())
+}
+
+fn dry_run_component(world: &mut World, prev: TypeId, to: TypeId) -> Option<ComponentMigrated> {
+    let entities = entities_with(world, prev);
+    if entities.is_empty() {
+        return None;
+    }
+    let diff = {
+        let registry = world.resource::<AppTypeRegistry>().read();
+        FieldDiff::new(registry.get_type_info(prev), registry.get_type_info(to))
+    };
+    Some(ComponentMigrated {
+        from_name: type_name(world, prev),
+        to_name: type_name(world, to),
+        entities,
+        fields_added: diff.added,
+        fields_removed: diff.removed,
+        fields_failed: diff.changed,
+        dry_run: true,
+        duration: Duration::ZERO,
+    })
+}
+
+fn migrate_component(world: &mut World, prev: TypeId, to: TypeId) -> Option<ComponentMigrated> {
+    let _span = info_span!("hot_patch_migrate").entered();
+    let start = Instant::now();
+    let mut entities = entities_with(world, prev);
+    if entities.is_empty() {
+        return None;
+    }
+    // Migrate entities archetype by archetype, so that inserting the new definition
+    // can reuse the archetype lookup for all entities of an archetype
+    entities.sort_by_key(|entity| {
+        world
+            .entities()
+            .get(*entity)
+            .map(|location| location.archetype_id)
+    });
+    let from_name = type_name(world, prev);
+    let to_name = type_name(world, to);
+
+    world.resource_scope::<AppTypeRegistry, _>(|world, registry| {
+        let registry = registry.read();
+        let name = &from_name;
         let Some(prev_reflect_component) = registry.get_type_data::<ReflectComponent>(prev) else {
             warn!("Component '{name}' needs to `#[reflect(Component)]`");
-            return;
+            return None;
         };
-        let Some(reflect_default) = registry.get_type_data::<ReflectDefault>(to) else {
-            warn!("Component '{name}' needs to `#[reflect(Default)]`");
-            return;
+        let Some(prev_reflect_migrate) = registry.get_type_data::<ReflectHotPatchMigrate>(prev)
+        else {
+            warn!("Component '{name}' needs to `#[reflect(HotPatchMigrate)]`");
+            return None;
         };
-        let Some(reflect_component) = registry.get_type_data::<ReflectComponent>(to) else {
-            warn!("Component '{name}' needs to `#[reflect(Component)]`");
-            return;
+        let Some(reflect_migrate) = registry.get_type_data::<ReflectHotPatchMigrate>(to) else {
+            warn!("Component '{name}' needs to `#[reflect(HotPatchMigrate)]`");
+            return None;
         };
+        let Some(to_info) = registry.get_type_info(to) else {
+            warn!("Component '{to_name}' needs to be registered");
+            return None;
+        };
+        let reflect_default = registry.get_type_data::<ReflectDefault>(to);
+        let diff = FieldDiff::new(registry.get_type_info(prev), Some(to_info));
 
-        // Migrate each entity that contains a component matching `from` type id
-        let mut builder = QueryBuilder::<Entity>::new(world);
-        builder.with_id(from_component_id);
-        let mut query = builder.build();
-
-        let entities: Vec<_> = query.iter(world).collect();
-
+        let mut values = Vec::with_capacity(entities.len());
+        let mut fields_failed = Vec::new();
         for entity in entities {
-            let entity_mut = world.entity_mut(entity);
-            let Some(prev_value) = prev_reflect_component.reflect(&entity_mut) else {
-                let name = world
-                    .components()
-                    .get_name(from_component_id)
-                    .unwrap_or_else(|| "Unknown".into());
+            let Some(prev_value) = prev_reflect_component.reflect(world.entity(entity)) else {
                 warn!("Tried to migrate entity {entity} but it didn't contain component '{name}'");
                 continue;
             };
 
+            let value = build_value(
+                prev_value.as_partial_reflect(),
+                to_info,
+                reflect_migrate,
+                reflect_default,
+                &registry,
+                |field, err| match field {
+                    Some(field) => {
+                        if !fields_failed.iter().any(|failed| failed == field) {
+                            warn!("Tried to migrate field '{field}' of component '{name}' but operation wasn't supported: {err}. Field will contain its default value.");
+                            fields_failed.push(field.to_string());
+                        }
+                    }
+                    None => {
+                        warn!("Tried to migrate component '{name}' on entity {entity} but operation wasn't supported: {err}. New component will contain default values.");
+                    }
+                },
+            );
+            match value {
+                Ok(value) => values.push((entity, value)),
+                Err(err) => {
+                    warn!("Tried to migrate component '{name}' on entity {entity} but {err}");
+                }
+            }
+        }
+
+        let migrated: Vec<_> = values.iter().map(|(entity, _)| *entity).collect();
+        // Insert the new definition before removing the old one, so that relationships
+        // are linked through the new type before the old one unlinks them.
+        // This also inserts the components required by the new definition.
+        let insert_start = Instant::now();
+        (reflect_migrate.insert_batch)(world, values);
+        let remove_start = Instant::now();
+        (prev_reflect_migrate.remove_batch)(world, &migrated);
+
+        let duration = start.elapsed();
+        debug!(
+            "Migrated {} entities from '{from_name}' to '{to_name}' in {duration:?} \
+            ({:?} inserting the new definition, {:?} removing the old one)",
+            migrated.len(),
+            remove_start - insert_start,
+            remove_start.elapsed(),
+        );
+        Some(ComponentMigrated {
+            from_name,
+            to_name,
+            entities: migrated,
+            fields_added: diff.added,
+            fields_removed: diff.removed,
+            fields_failed,
+            dry_run: false,
+            duration,
+        })
+    })
+}
+
+/// Builds the new definition of a component from its previous value.
+///
+/// Fields are taken from the previous value if they still exist with the same type. Otherwise,
+/// they are taken from `#[hot_migrate(default = ...)]` or from the reflected `Default`.
+/// `on_error` is called with the name of fields that exist in both definitions but changed their type
+/// or could not be copied, or with `None` if the whole value could not be applied and the default value
+/// is used instead.
+fn build_value(
+    prev: &dyn PartialReflect,
+    to: &'static TypeInfo,
+    reflect_migrate: &ReflectHotPatchMigrate,
+    reflect_default: Option<&ReflectDefault>,
+    registry: &TypeRegistry,
+    mut on_error: impl FnMut(Option<&str>, &dyn Display),
+) -> Result<Box<dyn Reflect>, String> {
+    let value: Box<dyn PartialReflect> = match to {
+        TypeInfo::Struct(_) | TypeInfo::TupleStruct(_) => {
+            let default = reflect_default.map(ReflectDefault::default);
+            let mut field_values = Vec::new();
+            for (field, type_path) in fields(to) {
+                let prev_field = field_of(prev, &field);
+                let prev_field = match prev_field {
+                    Some(prev_field) if prev_field.reflect_type_path() != type_path => {
+                        let err = ApplyError::MismatchedTypes {
+                            from_type: prev_field.reflect_type_path().into(),
+                            to_type: type_path.into(),
+                        };
+                        on_error(Some(&field), &err);
+                        None
+                    }
+                    prev_field => prev_field,
+                };
+                let prev_field = prev_field.and_then(|prev_field| {
+                    to_dynamic(prev_field, registry)
+                        .map_err(|err| on_error(Some(&field), &err))
+                        .ok()
+                });
+                let value = prev_field
+                    .or_else(|| (reflect_migrate.default_field)(&field))
+                    .or_else(|| {
+                        let default = default.as_deref()?.as_partial_reflect();
+                        to_dynamic(field_of(default, &field)?, registry).ok()
+                    })
+                    .ok_or_else(|| {
+                        format!("there is no value for field '{field}'. Add `#[hot_migrate(default = ...)]` to the field or `#[reflect(Default)]` to the component.")
+                    })?;
+                field_values.push((field, value));
+            }
+            if let TypeInfo::Struct(_) = to {
+                let mut dynamic = DynamicStruct::default();
+                dynamic.set_represented_type(Some(to));
+                for (field, value) in field_values {
+                    dynamic.insert_boxed(field, value);
+                }
+                Box::new(dynamic)
+            } else {
+                let mut dynamic = DynamicTupleStruct::default();
+                dynamic.set_represented_type(Some(to));
+                for (_, value) in field_values {
+                    dynamic.insert_boxed(value);
+                }
+                Box::new(dynamic)
+            }
+        }
+        _ => {
+            // Other kinds of components can only be applied onto their default value as a whole
+            let Some(reflect_default) = reflect_default else {
+                return Err("it needs to `#[reflect(Default)]`".to_string());
+            };
             let mut value = reflect_default.default();
-            if let Err(err) = value.try_apply(prev_value) {
-                let name = world
-                    .components()
-                    .get_name(from_component_id)
-                    .unwrap_or_else(|| "Unknown".into());
-                warn!("Tried to migrate component '{name}' on entity {entity} but operation wasn't supported: {err}. New component will contain default values.");
+            if let Err(err) = value.try_apply(prev) {
+                on_error(None, &err);
+                value = reflect_default.default();
             }
+            value.into_partial_reflect()
+        }
+    };
+    (reflect_migrate.from_reflect)(value.as_ref())
+        .ok_or_else(|| "the new definition could not be built from the previous one".to_string())
+}
 
-            let mut entity_mut = world.entity_mut(entity);
-            prev_reflect_component.remove(&mut entity_mut);
-            reflect_component.insert(&mut entity_mut, value.as_partial_reflect(), &registry);
+/// Copies `value` into a dynamic value, like [`PartialReflect::to_dynamic`] does.
+/// `to_dynamic` panics on opaque values that can't be cloned through reflection, so those are cloned
+/// or built with their registered [`ReflectFromReflect`] instead, and fail the copy if neither works.
+fn to_dynamic(
+    value: &dyn PartialReflect,
+    registry: &TypeRegistry,
+) -> Result<Box<dyn PartialReflect>, ReflectCloneError> {
+    let info = value.get_represented_type_info();
+    Ok(match value.reflect_ref() {
+        ReflectRef::Struct(value) => {
+            let mut dynamic = DynamicStruct::default();
+            dynamic.set_represented_type(info);
+            for (index, field) in value.iter_fields().enumerate() {
+                let name = value.name_at(index).unwrap_or_default().to_string();
+                dynamic.insert_boxed(name, to_dynamic(field, registry)?);
+            }
+            Box::new(dynamic)
         }
-    });
+        ReflectRef::TupleStruct(value) => {
+            let mut dynamic = DynamicTupleStruct::default();
+            dynamic.set_represented_type(info);
+            for field in value.iter_fields() {
+                dynamic.insert_boxed(to_dynamic(field, registry)?);
+            }
+            Box::new(dynamic)
+        }
+        ReflectRef::Tuple(value) => {
+            let mut dynamic = DynamicTuple::default();
+            dynamic.set_represented_type(info);
+            for field in value.iter_fields() {
+                dynamic.insert_boxed(to_dynamic(field, registry)?);
+            }
+            Box::new(dynamic)
+        }
+        ReflectRef::List(value) => {
+            let mut dynamic = DynamicList::default();
+            dynamic.set_represented_type(info);
+            for item in value.iter() {
+                dynamic.push_box(to_dynamic(item, registry)?);
+            }
+            Box::new(dynamic)
+        }
+        ReflectRef::Array(value) => {
+            let items = value
+                .iter()
+                .map(|item| to_dynamic(item, registry))
+                .collect::<Result<_, _>>()?;
+            let mut dynamic = DynamicArray::new(items);
+            dynamic.set_represented_type(info);
+            Box::new(dynamic)
+        }
+        ReflectRef::Map(value) => {
+            let mut dynamic = DynamicMap::default();
+            dynamic.set_represented_type(info);
+            for (key, value) in value.iter() {
+                dynamic.insert_boxed(to_dynamic(key, registry)?, to_dynamic(value, registry)?);
+            }
+            Box::new(dynamic)
+        }
+        ReflectRef::Set(value) => {
+            let mut dynamic = DynamicSet::default();
+            dynamic.set_represented_type(info);
+            for item in value.iter() {
+                dynamic.insert_boxed(to_dynamic(item, registry)?);
+            }
+            Box::new(dynamic)
+        }
+        ReflectRef::Enum(value) => {
+            let variant = match value.variant_type() {
+                VariantType::Unit => DynamicVariant::Unit,
+                VariantType::Tuple => {
+                    let mut fields = DynamicTuple::default();
+                    for field in value.iter_fields() {
+                        fields.insert_boxed(to_dynamic(field.value(), registry)?);
+                    }
+                    DynamicVariant::Tuple(fields)
+                }
+                VariantType::Struct => {
+                    let mut fields = DynamicStruct::default();
+                    for field in value.iter_fields() {
+                        let name = field.name().unwrap_or_default().to_string();
+                        fields.insert_boxed(name, to_dynamic(field.value(), registry)?);
+                    }
+                    DynamicVariant::Struct(fields)
+                }
+            };
+            let mut dynamic = DynamicEnum::new_with_index(
+                value.variant_index(),
+                value.variant_name().to_string(),
+                variant,
+            );
+            dynamic.set_represented_type(info);
+            Box::new(dynamic)
+        }
+        _ => value
+            .reflect_clone()
+            .or_else(|err| {
+                info.and_then(|info| registry.get_type_data::<ReflectFromReflect>(info.type_id()))
+                    .and_then(|reflect_from_reflect| reflect_from_reflect.from_reflect(value))
+                    .ok_or(err)
+            })?
+            .into_partial_reflect(),
+    })
+}
+
+/// Field of a struct by name or of a tuple struct by index.
+fn field_of<'a>(value: &'a dyn PartialReflect, field: &str) -> Option<&'a dyn PartialReflect> {
+    match value.reflect_ref() {
+        ReflectRef::Struct(value) => value.field(field),
+        ReflectRef::TupleStruct(value) => value.field(field.parse().ok()?),
+        _ => None,
+    }
 }
@@ -0,0 +1,466 @@
+//! Sources of hot patches.
+//!
+//! By default, [`SimpleSubsecondPlugin`](crate::SimpleSubsecondPlugin) receives hot patches from the devserver
+//! started by `dx serve --hot-patch`. Use [`SimpleSubsecondPlugin::with_patch_source`](crate::SimpleSubsecondPlugin::with_patch_source)
+//! to drive hot patches from your own build tooling or from tests instead.
+
+use std::{
+    io::{BufRead, BufReader, Read},
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+};
+
+use bevy_log::{error, warn};
+pub use dioxus_devtools::DevserverMsg;
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+use dioxus_devtools::{HotReloadMsg, subsecond::JumpTable};
+
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+use crate::HotPatchFailedReason;
+
+/// Delivers [`DevserverMsg`]s to the app, e.g. hot patches and build status updates.
+pub trait PatchSource: Send + Sync + 'static {
+    /// Starts delivering messages to `on_message`. Called once when the plugin is built.
+    ///
+    /// Must not block: sources that wait for messages should do so on a thread of their own.
+    fn connect(self: Box<Self>, on_message: Box<dyn FnMut(DevserverMsg) + Send>);
+}
+
+/// How many messages the [`PatchSource`] delivered so far, counted on the thread it delivers them on.
+/// Whether a source is connected can't be observed otherwise, e.g. the devserver's connection fails silently.
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+#[derive(bevy_ecs::resource::Resource, Debug, Clone, Default)]
+pub(crate) struct PatchSourceActivity(pub(crate) std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+/// Receives messages from the devserver started by `dx serve --hot-patch` over its websocket.
+/// This is the default [`PatchSource`].
+#[derive(Debug, Default, Clone)]
+pub struct DevserverPatchSource;
+
+impl PatchSource for DevserverPatchSource {
+    fn connect(self: Box<Self>, on_message: Box<dyn FnMut(DevserverMsg) + Send>) {
+        #[cfg(not(target_family = "wasm"))]
+        dioxus_devtools::connect(on_message);
+        #[cfg(target_family = "wasm")]
+        let _ = on_message;
+    }
+}
+
+/// Receives messages sent from within the process through a [`crossbeam_channel::Sender`].
+///
+/// ```ignore
+/// let (sender, source) = ChannelPatchSource::new();
+/// app.add_plugins(SimpleSubsecondPlugin::default().with_patch_source(source));
+/// sender.send(DevserverMsg::HotPatchStart).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ChannelPatchSource {
+    receiver: crossbeam_channel::Receiver<DevserverMsg>,
+}
+
+impl ChannelPatchSource {
+    /// Creates a source along with the sender that feeds it.
+    pub fn new() -> (crossbeam_channel::Sender<DevserverMsg>, Self) {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        (sender, Self { receiver })
+    }
+
+    /// Creates a source from an existing receiver.
+    pub fn from_receiver(receiver: crossbeam_channel::Receiver<DevserverMsg>) -> Self {
+        Self { receiver }
+    }
+}
+
+impl PatchSource for ChannelPatchSource {
+    fn connect(self: Box<Self>, mut on_message: Box<dyn FnMut(DevserverMsg) + Send>) {
+        std::thread::spawn(move || {
+            // Ends when all senders are dropped
+            for msg in self.receiver {
+                on_message(msg);
+            }
+        });
+    }
+}
+
+/// Listens on a TCP address for connections that send [`DevserverMsg`]s as JSON, one message per line.
+/// Connections are handled one after the other.
+///
+/// # Security
+///
+/// A hot patch makes the app load and run the library it points to, so anything that can connect to the
+/// address can run arbitrary code in the app. Only loopback addresses are listened on by default:
+/// any other address is refused unless [`allow_remote`](Self::allow_remote) is called.
+#[derive(Debug, Clone)]
+pub struct TcpPatchSource {
+    addr: SocketAddr,
+    allow_remote: bool,
+}
+
+impl TcpPatchSource {
+    /// Creates a source that listens on `addr`, e.g. `127.0.0.1:3001`.
+    pub fn new(addr: impl Into<SocketAddr>) -> Self {
+        Self {
+            addr: addr.into(),
+            allow_remote: false,
+        }
+    }
+
+    /// Allows listening on addresses other than loopback ones, e.g. to patch an app running on another device.
+    /// Everyone that can reach the address can then run arbitrary code in the app,
+    /// so only use this on networks you trust.
+    pub fn allow_remote(mut self) -> Self {
+        self.allow_remote = true;
+        self
+    }
+}
+
+impl PatchSource for TcpPatchSource {
+    fn connect(self: Box<Self>, mut on_message: Box<dyn FnMut(DevserverMsg) + Send>) {
+        if !self.addr.ip().is_loopback() && !self.allow_remote {
+            error!(
+                "Refusing to listen for hot patches on {}, as anyone reaching it could run code in the app. \
+                Use a loopback address or `TcpPatchSource::allow_remote`",
+                self.addr
+            );
+            return;
+        }
+        let listener = match TcpListener::bind(self.addr) {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Failed to listen for hot patches on {}: {err}", self.addr);
+                return;
+            }
+        };
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => read_messages(stream, &mut on_message),
+                    Err(err) => warn!("Failed to accept hot patch connection: {err}"),
+                }
+            }
+        });
+    }
+}
+
+/// Listens on a Unix socket for connections that send [`DevserverMsg`]s as JSON, one message per line.
+/// Connections are handled one after the other.
+///
+/// Anything that can connect to the socket can run arbitrary code in the app, see [`TcpPatchSource`].
+/// Access is controlled by the permissions of the socket file.
+#[derive(Debug, Clone)]
+pub struct UnixSocketPatchSource {
+    path: PathBuf,
+}
+
+impl UnixSocketPatchSource {
+    /// Creates a source that listens on the socket at `path`.
+    /// A socket left at `path` by a previous run is removed first.
+    /// If there is anything else at `path`, it is kept and the source does not listen.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl PatchSource for UnixSocketPatchSource {
+    #[cfg(unix)]
+    fn connect(self: Box<Self>, mut on_message: Box<dyn FnMut(DevserverMsg) + Send>) {
+        use std::os::unix::fs::FileTypeExt;
+
+        // Binding fails if the socket file is left over from a previous run
+        match std::fs::symlink_metadata(&self.path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                if let Err(err) = std::fs::remove_file(&self.path) {
+                    error!(
+                        "Failed to remove the stale socket {}: {err}",
+                        self.path.display()
+                    );
+                    return;
+                }
+            }
+            Ok(_) => {
+                error!(
+                    "Not listening for hot patches on {}, as there is a file that is not a socket",
+                    self.path.display()
+                );
+                return;
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                error!(
+                    "Failed to check {} for a stale socket: {err}",
+                    self.path.display()
+                );
+                return;
+            }
+        }
+        let listener = match std::os::unix::net::UnixListener::bind(&self.path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!(
+                    "Failed to listen for hot patches on {}: {err}",
+                    self.path.display()
+                );
+                return;
+            }
+        };
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => read_messages(stream, &mut on_message),
+                    Err(err) => warn!("Failed to accept hot patch connection: {err}"),
+                }
+            }
+        });
+    }
+
+    #[cfg(not(unix))]
+    fn connect(self: Box<Self>, _on_message: Box<dyn FnMut(DevserverMsg) + Send>) {
+        error!(
+            "Unix sockets are not supported on this platform, not listening for hot patches on {}",
+            self.path.display()
+        );
+    }
+}
+
+/// Identifies the file of the running executable, to notice it being rebuilt while the app runs.
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ExecutableIdentity {
+    len: u64,
+    modified: Option<std::time::SystemTime>,
+}
+
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+impl ExecutableIdentity {
+    /// The identity of the executable file as it is now, if it can be read.
+    pub(crate) fn current() -> Option<Self> {
+        let metadata = std::env::current_exe().and_then(std::fs::metadata).ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// The running app that patches are checked against by [`validate_patch`].
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PatchTarget {
+    build_id: u64,
+    pid: u32,
+    aslr_reference: u64,
+    /// The executable when the app started.
+    started_with: Option<ExecutableIdentity>,
+    /// The executable as it is now.
+    executable: Option<ExecutableIdentity>,
+}
+
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+impl PatchTarget {
+    /// The running app, which started with the executable identified by `started_with`.
+    pub(crate) fn running(started_with: Option<ExecutableIdentity>) -> Self {
+        Self {
+            build_id: std::env::var("DIOXUS_BUILD_ID")
+                .ok()
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(0),
+            pid: std::process::id(),
+            aslr_reference: dioxus_devtools::subsecond::aslr_reference() as u64,
+            started_with,
+            executable: ExecutableIdentity::current(),
+        }
+    }
+}
+
+/// Checks that a patch was built for this process and executable.
+/// Applying a patch built for another executable jumps into arbitrary code.
+#[cfg(all(not(target_family = "wasm"), debug_assertions))]
+pub(crate) fn validate_patch(
+    msg: &HotReloadMsg,
+    jump_table: &JumpTable,
+    target: &PatchTarget,
+) -> Result<(), HotPatchFailedReason> {
+    match msg.for_build_id {
+        Some(patch) if patch != target.build_id => {
+            return Err(HotPatchFailedReason::WrongBuild {
+                patch,
+                running: target.build_id,
+            });
+        }
+        Some(_) => {}
+        // Sources other than the devserver may not know which build they target.
+        // Patches they build after the executable was rebuilt are linked against the new one
+        None if target.started_with.is_some() && target.executable != target.started_with => {
+            return Err(HotPatchFailedReason::ExecutableChanged);
+        }
+        None => {}
+    }
+    if let Some(patch) = msg.for_pid.filter(|&patch| patch != target.pid) {
+        return Err(HotPatchFailedReason::WrongProcess {
+            patch,
+            running: target.pid,
+        });
+    }
+
+    // The executable is loaded at a page-aligned offset from the address the patch was linked against.
+    // Anything else means the patch was linked against a different binary.
+    // subsecond only supports offsets upwards, as it subtracts the patch's address from the running one
+    const PAGE_SIZE: u64 = 4096;
+    let running = target.aslr_reference;
+    let patch = jump_table.aslr_reference;
+    if running < patch || !running.abs_diff(patch).is_multiple_of(PAGE_SIZE) {
+        return Err(HotPatchFailedReason::BaseAddressMismatch { patch, running });
+    }
+
+    if !jump_table.lib.exists() {
+        return Err(HotPatchFailedReason::MissingLibrary(jump_table.lib.clone()));
+    }
+    Ok(())
+}
+
+/// Reads newline-delimited JSON messages from `stream` until it is closed.
+fn read_messages(stream: impl Read, on_message: &mut dyn FnMut(DevserverMsg)) {
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                warn!("Hot patch connection failed: {err}");
+                return;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(msg) => on_message(msg),
+            Err(err) => warn!("Ignoring malformed hot patch message: {err}"),
+        }
+    }
+}
+
+#[cfg(all(test, not(target_family = "wasm"), debug_assertions))]
+mod tests {
+    use std::{path::PathBuf, time::SystemTime};
+
+    use dioxus_devtools::{HotReloadMsg, subsecond::JumpTable};
+
+    use super::{ExecutableIdentity, PatchTarget, validate_patch};
+    use crate::HotPatchFailedReason;
+
+    const EXECUTABLE: ExecutableIdentity = ExecutableIdentity {
+        len: 1024,
+        modified: Some(SystemTime::UNIX_EPOCH),
+    };
+
+    fn target() -> PatchTarget {
+        PatchTarget {
+            build_id: 7,
+            pid: 42,
+            aslr_reference: 0x5555_5555_0000,
+            started_with: Some(EXECUTABLE),
+            executable: Some(EXECUTABLE),
+        }
+    }
+
+    fn jump_table(aslr_reference: u64) -> JumpTable {
+        JumpTable {
+            // Any file that exists
+            lib: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"),
+            map: Default::default(),
+            aslr_reference,
+            new_base_address: 0,
+            ifunc_count: 0,
+        }
+    }
+
+    fn msg(for_build_id: Option<u64>, for_pid: Option<u32>) -> HotReloadMsg {
+        HotReloadMsg {
+            for_build_id,
+            for_pid,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_patch_for_this_build_and_process() {
+        let table = jump_table(0x1000);
+        assert_eq!(
+            validate_patch(&msg(Some(7), Some(42)), &table, &target()),
+            Ok(())
+        );
+        assert_eq!(validate_patch(&msg(None, None), &table, &target()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_other_build_and_process() {
+        let table = jump_table(0x1000);
+        assert_eq!(
+            validate_patch(&msg(Some(8), Some(42)), &table, &target()),
+            Err(HotPatchFailedReason::WrongBuild {
+                patch: 8,
+                running: 7
+            })
+        );
+        assert_eq!(
+            validate_patch(&msg(Some(7), Some(43)), &table, &target()),
+            Err(HotPatchFailedReason::WrongProcess {
+                patch: 43,
+                running: 42
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_patch_without_build_after_executable_changed() {
+        let table = jump_table(0x1000);
+        let rebuilt = PatchTarget {
+            executable: Some(ExecutableIdentity {
+                len: 2048,
+                ..EXECUTABLE
+            }),
+            ..target()
+        };
+        assert_eq!(
+            validate_patch(&msg(None, None), &table, &rebuilt),
+            Err(HotPatchFailedReason::ExecutableChanged)
+        );
+        // Patches that name their build are checked against it instead
+        assert_eq!(
+            validate_patch(&msg(Some(7), None), &table, &rebuilt),
+            Ok(())
+        );
+        // Nothing to compare against if the executable could not be read at startup
+        let unknown = PatchTarget {
+            started_with: None,
+            ..rebuilt
+        };
+        assert_eq!(validate_patch(&msg(None, None), &table, &unknown), Ok(()));
+    }
+
+    #[test]
+    fn rejects_unaligned_or_downward_base_address() {
+        let running = target().aslr_reference;
+        for patch in [running + 1, running - 0x10, running + 0x1000] {
+            assert_eq!(
+                validate_patch(&msg(None, None), &jump_table(patch), &target()),
+                Err(HotPatchFailedReason::BaseAddressMismatch { patch, running })
+            );
+        }
+        assert_eq!(
+            validate_patch(&msg(None, None), &jump_table(running), &target()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_missing_library() {
+        let table = JumpTable {
+            lib: PathBuf::from("/nonexistent/libpatch.so"),
+            ..jump_table(0x1000)
+        };
+        assert_eq!(
+            validate_patch(&msg(None, None), &table, &target()),
+            Err(HotPatchFailedReason::MissingLibrary(table.lib.clone()))
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Compares the execution time of `#[hot]` systems before and after a hot patch.
+
+use core::time::Duration;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{LazyLock, Mutex},
+    time::Instant,
+};
+
+use bevy_ecs::prelude::*;
+use bevy_log::info;
+use dioxus_devtools::subsecond::HotFnPtr;
+
+use crate::{HotSystemTiming, lock};
+
+/// Number of runs averaged into the execution time of a version of a system.
+const SAMPLES: usize = 60;
+
+struct SystemProfile {
+    /// The version of the system the samples belong to.
+    ptr: HotFnPtr,
+    samples: VecDeque<Duration>,
+    /// Average execution time of the previous version, until enough samples of the current one are collected.
+    before: Option<Duration>,
+}
+
+impl SystemProfile {
+    fn average(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len().max(1) as u32
+    }
+}
+
+static PROFILES: LazyLock<Mutex<HashMap<&'static str, SystemProfile>>> =
+    LazyLock::new(Default::default);
+/// Comparisons completed since the last frame.
+static TIMINGS: Mutex<Vec<HotSystemTiming>> = Mutex::new(Vec::new());
+
+/// Calls `call`, recording how long it took.
+pub(crate) fn profile<R>(system: &'static str, ptr: HotFnPtr, call: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = call();
+    record(system, ptr, start.elapsed());
+    result
+}
+
+fn record(system: &'static str, ptr: HotFnPtr, duration: Duration) {
+    let mut profiles = lock(&PROFILES);
+    let profile = profiles.entry(system).or_insert_with(|| SystemProfile {
+        ptr,
+        samples: VecDeque::with_capacity(SAMPLES),
+        before: None,
+    });
+    if profile.ptr != ptr {
+        // Keep comparing against the last fully measured version if the system is patched again in quick succession
+        if profile.before.is_none() || profile.samples.len() == SAMPLES {
+            profile.before = (!profile.samples.is_empty()).then(|| profile.average());
+        }
+        profile.samples.clear();
+        profile.ptr = ptr;
+    }
+    if profile.samples.len() == SAMPLES {
+        profile.samples.pop_front();
+    }
+    profile.samples.push_back(duration);
+
+    if profile.samples.len() == SAMPLES {
+        if let Some(before) = profile.before.take() {
+            lock(&TIMINGS).push(HotSystemTiming {
+                name: system,
+                before,
+                after: profile.average(),
+            });
+        }
+    }
+}
+
+pub(crate) fn send_timings(mut events: EventWriter<HotSystemTiming>) {
+    let timings = core::mem::take(&mut *lock(&TIMINGS));
+    for timing in &timings {
+        let change = (timing.after.as_secs_f64() / timing.before.as_secs_f64().max(f64::EPSILON)
+            - 1.0)
+            * 100.0;
+        info!(
+            "{} took {:?} after the hot patch, {change:+.1}% compared to {:?} before",
+            timing.name, timing.after, timing.before
+        );
+    }
+    events.write_batch(timings);
+}
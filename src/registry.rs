@@ -0,0 +1,206 @@
+//! Introspection of the systems annotated with `#[hot]`.
+
+use std::{
+    collections::BTreeSet,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use bevy_ecs::prelude::*;
+use bevy_log::{debug, info_span};
+use dioxus_devtools::subsecond::HotFnPtr;
+
+use crate::{diagnostics::HotPatchTimings, history::HotPatchHistory, lock};
+
+/// The systems annotated with `#[hot]` that ran at least once, e.g. for showing them in a debug UI:
+///
+/// ```ignore
+/// fn list_hot_systems(registry: Res<HotSystemRegistry>) {
+///     for system in registry.iter() {
+///         info!("{} ({}:{}) was patched {} times", system.name, system.file, system.lines.0, system.patch_count);
+///     }
+/// }
+/// ```
+#[derive(Resource, Debug, Default)]
+pub struct HotSystemRegistry {
+    systems: Vec<HotSystemInfo>,
+}
+
+impl HotSystemRegistry {
+    /// Iterates over the hot systems in the order in which they first ran.
+    pub fn iter(&self) -> impl Iterator<Item = &HotSystemInfo> {
+        self.systems.iter()
+    }
+
+    /// Returns the hot system with the given name, e.g. `my_game::ui::spawn_menu`.
+    pub fn get(&self, name: &str) -> Option<&HotSystemInfo> {
+        self.systems.iter().find(|system| system.name == name)
+    }
+
+    /// The number of hot systems that ran at least once.
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    /// Whether no hot system ran yet.
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    /// Reruns the `#[hot(rerun_on_hot_patch = true)]` system with the given name as if it had been hot-patched.
+    /// Returns `false` if there is no such system.
+    pub fn rerun(world: &mut World, name: &str) -> bool {
+        let system = world
+            .get_resource::<Self>()
+            .and_then(|registry| registry.get(name))
+            .and_then(|system| Some((system.name, system.rerun?)));
+        let Some((name, rerun)) = system else {
+            return false;
+        };
+        debug!("Rerunning hot system {name}");
+        run_rerun(world, name, rerun);
+        true
+    }
+}
+
+/// A system annotated with `#[hot]`, see [`HotSystemRegistry`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct HotSystemInfo {
+    /// The system's name, e.g. `my_game::ui::spawn_menu`.
+    /// Each instantiation of a generic system is a system of its own, e.g. `my_game::sync<my_game::Player>`.
+    pub name: &'static str,
+    /// The file the system is defined in.
+    pub file: &'static str,
+    /// The first and last line of the system's definition when the app was launched.
+    pub lines: (u32, u32),
+    /// How the system reacts to hot patches.
+    pub mode: HotSystemMode,
+    /// Pointer to the current version of the system's code.
+    pub current_ptr: HotFnPtr,
+    /// How often the system's code changed since it first ran.
+    pub patch_count: usize,
+    /// The [`HotPatchHistory::generation`] in which the system's code last changed,
+    /// or `None` if it didn't change since it first ran.
+    pub last_generation: Option<usize>,
+    get_ptr: fn() -> HotFnPtr,
+    rerun: Option<fn(&mut World)>,
+}
+
+/// How a `#[hot]` system reacts to hot patches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotSystemMode {
+    /// `#[hot]`: the new code is used the next time the system runs.
+    Plain,
+    /// `#[hot(hot_patch_signature = true)]`: the system's parameters may change.
+    HotPatchSignature,
+    /// `#[hot(rerun_on_hot_patch = true)]`: the system is rerun when its code changes.
+    RerunOnHotPatch,
+}
+
+/// Hot systems that ran for the first time since the registry was last updated.
+static NEW_SYSTEMS: std::sync::Mutex<Vec<HotSystemInfo>> = std::sync::Mutex::new(Vec::new());
+/// Whether `NEW_SYSTEMS` may contain systems, so that the registry usually doesn't need to lock it.
+static HAS_NEW_SYSTEMS: AtomicBool = AtomicBool::new(false);
+
+/// The names of the hot systems registered so far.
+static REGISTERED: std::sync::Mutex<BTreeSet<&'static str>> =
+    std::sync::Mutex::new(BTreeSet::new());
+
+/// The names of the hot systems registered so far, in an open-addressing table indexed by their address,
+/// so that generic systems can check whether they were registered without locking `REGISTERED`.
+/// Once it is full, the systems that don't fit are looked up in `REGISTERED` instead.
+static REGISTERED_NAMES: [OnceLock<&'static str>; 256] = [const { OnceLock::new() }; 256];
+
+/// Called by `#[hot]` systems the first time they run.
+/// Generic systems call it every time they run, as each instantiation is a system of its own,
+/// so systems that were registered already are skipped, usually without taking a lock.
+pub(crate) fn register(
+    name: &'static str,
+    file: &'static str,
+    lines: (u32, u32),
+    mode: HotSystemMode,
+    get_ptr: fn() -> HotFnPtr,
+    rerun: Option<fn(&mut World)>,
+) {
+    let start = name.as_ptr().addr() % REGISTERED_NAMES.len();
+    for offset in 0..REGISTERED_NAMES.len() {
+        let slot = &REGISTERED_NAMES[(start + offset) % REGISTERED_NAMES.len()];
+        match slot.get() {
+            Some(&registered) if registered == name => return,
+            Some(_) => {}
+            None => match slot.set(name) {
+                Ok(()) => break,
+                // Another thread claimed the slot first, possibly for the same system
+                Err(_) if slot.get() == Some(&name) => return,
+                Err(_) => {}
+            },
+        }
+    }
+    if !lock(&REGISTERED).insert(name) {
+        return;
+    }
+    lock(&NEW_SYSTEMS).push(HotSystemInfo {
+        name,
+        file,
+        lines,
+        mode,
+        current_ptr: get_ptr(),
+        patch_count: 0,
+        last_generation: None,
+        get_ptr,
+        rerun,
+    });
+    HAS_NEW_SYSTEMS.store(true, Ordering::Release);
+}
+
+pub(crate) fn add_new_systems(mut registry: ResMut<HotSystemRegistry>) {
+    if !HAS_NEW_SYSTEMS.swap(false, Ordering::Acquire) {
+        return;
+    }
+    registry.systems.append(&mut lock(&NEW_SYSTEMS));
+}
+
+/// Checks which hot systems changed after a hot patch and reruns the ones with `#[hot(rerun_on_hot_patch = true)]`.
+/// Only runs when [`HotPatched`](crate::HotPatched) was sent, so hot systems cost nothing between hot patches.
+pub(crate) fn dispatch(world: &mut World) {
+    let generation = world
+        .get_resource::<HotPatchHistory>()
+        .map(HotPatchHistory::generation);
+    let Some(mut registry) = world.get_resource_mut::<HotSystemRegistry>() else {
+        return;
+    };
+    let mut reruns = Vec::new();
+    for system in &mut registry.systems {
+        let ptr = (system.get_ptr)();
+        if ptr == system.current_ptr {
+            continue;
+        }
+        system.current_ptr = ptr;
+        system.patch_count += 1;
+        system.last_generation = generation;
+        match system.rerun {
+            Some(rerun) => {
+                debug!("Hot-patched and rerunning system {}", system.name);
+                reruns.push((system.name, rerun));
+            }
+            None => debug!("Hot-patched system {}", system.name),
+        }
+    }
+    // The registry can't be borrowed while the systems run, as they may register other hot systems
+    for (name, rerun) in reruns {
+        run_rerun(world, name, rerun);
+    }
+}
+
+fn run_rerun(world: &mut World, system: &'static str, rerun: fn(&mut World)) {
+    let _span = info_span!("hot_patch_rerun", system).entered();
+    let start = Instant::now();
+    rerun(world);
+    if let Some(mut timings) = world.get_resource_mut::<HotPatchTimings>() {
+        timings.record_rerun(start.elapsed());
+    }
+}
@@ -0,0 +1,224 @@
+//! [Bevy Remote Protocol](bevy_remote) methods for hot patching.
+//!
+//! Registered when both the `remote` feature is enabled and the [`RemotePlugin`](bevy_remote::RemotePlugin) is added.
+
+use std::{
+    sync::atomic::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy_app::{App, PreUpdate};
+use bevy_ecs::prelude::*;
+use bevy_remote::{BrpError, BrpResult, RemoteMethodSystemId, RemoteMethods, error_codes};
+use serde_json::{Value, json};
+
+use crate::{
+    DevserverShutdown, HotBuildFailed, HotBuildStarted, HotPatchFailed, HotPatched,
+    history::HotPatchHistory,
+    hot_patched_app,
+    patch_source::PatchSourceActivity,
+    registry::{HotSystemMode, HotSystemRegistry},
+};
+
+/// Lists the `#[hot]` systems that ran at least once and how often they were patched.
+///
+/// `params`: none.
+///
+/// `result`: an array of `{ "name", "file", "lines": [first, last], "mode", "patch_count", "last_generation" }`,
+/// where `mode` is one of `"plain"`, `"hot_patch_signature"` and `"rerun_on_hot_patch"`.
+pub const LIST_METHOD: &str = "hot/list";
+
+/// Reports whether the patch source delivers messages, whether a build is running and when the last patch was applied.
+///
+/// `params`: none.
+///
+/// `result`: `{ "connection", "messages_received", "last_message", "building", "shutdown", "generation", "last_patch", "last_failure" }`,
+/// where `connection` is `"waiting"` until the patch source delivered a message, then `"receiving"`,
+/// and `"closed"` once the devserver shut down. A source that stays `"waiting"` may not be connected,
+/// e.g. because the app was not launched by `dx serve --hot-patch`.
+/// `last_message` and `last_patch` are in seconds since the Unix epoch.
+pub const STATUS_METHOD: &str = "hot/status";
+
+/// Reruns a `#[hot(rerun_on_hot_patch = true)]` system or the [`StartupRerunHotPatch`](crate::hot_patched_app::StartupRerunHotPatch) schedules.
+///
+/// `params`: `{ "system": name }` to rerun a system,
+/// otherwise optionally `{ "section": name }` to only rerun the schedule of that hot-patched section.
+///
+/// `result`: `null` for a system, or an array of the names of the sections whose schedule was rerun.
+pub const RERUN_METHOD: &str = "hot/rerun";
+
+/// Lists the generations of code that [`HotPatchHistory`] can restore.
+///
+/// `params`: none.
+///
+/// `result`: `{ "generation", "oldest_generation", "latest_generation", "limit", "patches": [{ "generation", "library" }] }`.
+pub const HISTORY_METHOD: &str = "hot/history";
+
+/// What happened to the hot patches lately, for [`STATUS_METHOD`].
+#[derive(Resource, Default)]
+struct HotPatchStatus {
+    messages_received: usize,
+    last_message: Option<SystemTime>,
+    building: bool,
+    shutdown: bool,
+    last_patch: Option<SystemTime>,
+    last_failure: Option<String>,
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    if !app.world().contains_resource::<RemoteMethods>() {
+        return;
+    }
+    app.init_resource::<HotPatchStatus>()
+        .add_systems(PreUpdate, track_status);
+
+    let world = app.world_mut();
+    let methods = [
+        (LIST_METHOD, world.register_system(list)),
+        (STATUS_METHOD, world.register_system(status)),
+        (RERUN_METHOD, world.register_system(rerun)),
+        (HISTORY_METHOD, world.register_system(history)),
+    ];
+    let mut remote_methods = world.resource_mut::<RemoteMethods>();
+    for (name, system) in methods {
+        remote_methods.insert(name, RemoteMethodSystemId::Instant(system));
+    }
+}
+
+fn track_status(
+    mut status: ResMut<HotPatchStatus>,
+    mut build_started: EventReader<HotBuildStarted>,
+    mut build_failed: EventReader<HotBuildFailed>,
+    mut hot_patched: EventReader<HotPatched>,
+    mut patch_failed: EventReader<HotPatchFailed>,
+    mut shutdown: EventReader<DevserverShutdown>,
+    activity: Option<Res<PatchSourceActivity>>,
+) {
+    let received = activity.map_or(0, |activity| activity.0.load(Ordering::Relaxed));
+    if received > status.messages_received {
+        status.messages_received = received;
+        status.last_message = Some(SystemTime::now());
+    }
+    if build_started.read().count() > 0 {
+        status.building = true;
+    }
+    if build_failed.read().count() > 0 {
+        status.building = false;
+        status.last_failure = Some("build failed".to_string());
+    }
+    if let Some(failed) = patch_failed.read().last() {
+        status.building = false;
+        status.last_failure = Some(failed.reason.to_string());
+    }
+    if hot_patched.read().count() > 0 {
+        status.building = false;
+        status.last_patch = Some(SystemTime::now());
+    }
+    if shutdown.read().count() > 0 {
+        status.building = false;
+        status.shutdown = true;
+    }
+}
+
+fn list(In(_params): In<Option<Value>>, registry: Res<HotSystemRegistry>) -> BrpResult {
+    let systems: Vec<_> = registry
+        .iter()
+        .map(|system| {
+            json!({
+                "name": system.name,
+                "file": system.file,
+                "lines": [system.lines.0, system.lines.1],
+                "mode": match system.mode {
+                    HotSystemMode::Plain => "plain",
+                    HotSystemMode::HotPatchSignature => "hot_patch_signature",
+                    HotSystemMode::RerunOnHotPatch => "rerun_on_hot_patch",
+                },
+                "patch_count": system.patch_count,
+                "last_generation": system.last_generation,
+            })
+        })
+        .collect();
+    Ok(Value::Array(systems))
+}
+
+fn status(
+    In(_params): In<Option<Value>>,
+    status: Res<HotPatchStatus>,
+    history: Res<HotPatchHistory>,
+) -> BrpResult {
+    let seconds = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+    };
+    let connection = if status.shutdown {
+        "closed"
+    } else if status.messages_received > 0 {
+        "receiving"
+    } else {
+        "waiting"
+    };
+    Ok(json!({
+        "connection": connection,
+        "messages_received": status.messages_received,
+        "last_message": status.last_message.map(seconds),
+        "building": status.building,
+        "shutdown": status.shutdown,
+        "generation": history.generation(),
+        "last_patch": status.last_patch.map(seconds),
+        "last_failure": status.last_failure,
+    }))
+}
+
+fn rerun(In(params): In<Option<Value>>, world: &mut World) -> BrpResult {
+    let param = |key: &str| -> Result<Option<String>, BrpError> {
+        match params.as_ref().and_then(|params| params.get(key)) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(invalid_params(format!("`{key}` must be a string"))),
+        }
+    };
+    if let Some(system) = param("system")? {
+        if !HotSystemRegistry::rerun(world, &system) {
+            return Err(invalid_params(format!(
+                "No system annotated with `#[hot(rerun_on_hot_patch = true)]` named {system:?} has run yet"
+            )));
+        }
+        return Ok(Value::Null);
+    }
+    let section = param("section")?;
+    let sections = hot_patched_app::rerun_startup(world, section.as_deref());
+    if let Some(section) = section.filter(|_| sections.is_empty()) {
+        return Err(invalid_params(format!(
+            "No hot-patched section named {section:?} has a `StartupRerunHotPatch` schedule"
+        )));
+    }
+    Ok(json!(sections))
+}
+
+fn history(In(_params): In<Option<Value>>, history: Res<HotPatchHistory>) -> BrpResult {
+    let patches: Vec<_> = history
+        .patches()
+        .map(|(generation, library)| {
+            json!({
+                "generation": generation,
+                "library": library.display().to_string(),
+            })
+        })
+        .collect();
+    Ok(json!({
+        "generation": history.generation(),
+        "oldest_generation": history.oldest_generation(),
+        "latest_generation": history.latest_generation(),
+        "limit": history.limit(),
+        "patches": patches,
+    }))
+}
+
+fn invalid_params(message: String) -> BrpError {
+    BrpError {
+        code: error_codes::INVALID_PARAMS,
+        message,
+        data: None,
+    }
+}
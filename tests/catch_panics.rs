@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+use bevy_simple_subsecond_system::{patch_source::ChannelPatchSource, prelude::*};
+
+#[derive(Resource, Default)]
+struct Calls(u32);
+
+#[hot]
+fn broken(mut calls: ResMut<Calls>) {
+    calls.0 += 1;
+    panic!("broken system");
+}
+
+// Panicking systems are disabled in a static, so all apps in this test binary would share them
+#[test]
+fn panicking_systems_are_disabled_and_reported() {
+    let (_sender, source) = ChannelPatchSource::new();
+    let mut app = App::new();
+    app.add_plugins(
+        SimpleSubsecondPlugin::default()
+            .catch_panics(true)
+            .with_patch_source(source),
+    )
+    .init_resource::<Calls>()
+    .add_systems(Update, broken);
+
+    app.update();
+    let panicked: Vec<_> = app
+        .world_mut()
+        .resource_mut::<Events<HotSystemPanicked>>()
+        .drain()
+        .collect();
+    assert_eq!(panicked.len(), 1, "{panicked:?}");
+    assert!(panicked[0].name.ends_with("broken"), "{panicked:?}");
+    assert_eq!(panicked[0].message, "broken system");
+
+    app.update();
+    app.update();
+    assert_eq!(app.world().resource::<Calls>().0, 1);
+    assert!(
+        app.world()
+            .resource::<Events<HotSystemPanicked>>()
+            .is_empty()
+    );
+
+    // A hot patch that didn't change the system keeps it disabled
+    app.world_mut().send_event_default::<HotPatched>();
+    app.update();
+    assert_eq!(app.world().resource::<Calls>().0, 1);
+}
@@ -0,0 +1,66 @@
+use std::{any::TypeId, marker::PhantomData};
+
+use bevy::prelude::*;
+use bevy_simple_subsecond_system::prelude::*;
+
+#[derive(Reflect, Default)]
+struct Player;
+
+#[derive(Debug, Reflect, Component, HotPatchMigrate)]
+#[reflect(Component, HotPatchMigrate)]
+struct Health<T, V = f32>
+where
+    T: Reflect + TypePath + Default + Send + Sync + 'static,
+    V: Reflect + TypePath + FromReflect + Copy + From<u8> + Send + Sync + 'static,
+{
+    current: V,
+    #[hot_migrate(default = V::from(100))]
+    max: V,
+    #[hot_migrate(default = Vec::from([V::from(1), V::from(2)]))]
+    regeneration: Vec<V>,
+    #[reflect(ignore)]
+    _owner: PhantomData<T>,
+}
+
+#[derive(Debug, Reflect, Component, HotPatchMigrate)]
+#[reflect(Component, HotPatchMigrate)]
+struct Armor(#[hot_migrate(default = { 2 + 3 })] u32, f32);
+
+fn default_field<T: HotPatchMigrate, V: PartialReflect + Copy>(field: &str) -> Option<V> {
+    Some(*T::default_field(field)?.try_downcast_ref::<V>()?)
+}
+
+#[test]
+fn generic_components_can_be_migrated() {
+    App::new()
+        .add_plugins(SimpleSubsecondPlugin::default())
+        .register_type::<Health<Player>>()
+        .register_type::<Health<Player, f64>>()
+        .register_type::<Armor>();
+
+    assert_eq!(
+        Health::<Player>::current_type_id(),
+        TypeId::of::<Health<Player>>()
+    );
+    assert_ne!(
+        Health::<Player, f64>::current_type_id(),
+        Health::<Player>::current_type_id()
+    );
+}
+
+#[test]
+fn field_defaults_are_evaluated_for_each_field() {
+    assert_eq!(default_field::<Health<Player>, f32>("max"), Some(100.0));
+    assert_eq!(
+        default_field::<Health<Player, f64>, f64>("max"),
+        Some(100.0)
+    );
+    assert_eq!(
+        Health::<Player>::default_field("regeneration")
+            .and_then(|value| value.try_downcast_ref::<Vec<f32>>().cloned()),
+        Some(vec![1.0, 2.0])
+    );
+    assert!(Health::<Player>::default_field("current").is_none());
+    assert_eq!(default_field::<Armor, u32>("0"), Some(5));
+    assert!(Armor::default_field("1").is_none());
+}
@@ -47,6 +47,8 @@ fn add_to_app() {
                 exclusive,
                 force_loading_screen.pipe(ignore_progress),
                 wait_in_screen(1.0),
+                system_with_commands_despawn_spawned,
+                exclusive_mut_despawn_spawned,
             ),
         ),
     );
@@ -166,6 +168,12 @@ fn exclusive_mut(world: &mut World) {}
 #[hot(rerun_on_hot_patch = true)]
 fn exclusive(world: &World) {}
 
+#[hot(rerun_on_hot_patch = true, despawn_spawned = true)]
+fn system_with_commands_despawn_spawned(mut commands: Commands) {}
+
+#[hot(rerun_on_hot_patch = true, despawn_spawned = true)]
+fn exclusive_mut_despawn_spawned(world: &mut World) {}
+
 //#[hot(rerun_on_hot_patch = true)]
 fn force_loading_screen(config: ConfigRef<DevConfig>, screen: CurrentRef<Screen>) -> Progress {
     todo!()
@@ -0,0 +1,497 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicI32, Ordering},
+};
+
+use bevy::{ecs::system::SystemId, prelude::*};
+use bevy_simple_subsecond_system::prelude::*;
+
+#[derive(Resource, Default)]
+struct Runs(usize);
+
+fn count_runs(mut runs: ResMut<Runs>) {
+    runs.0 += 1;
+}
+
+fn app(section: impl FnMut(&mut App) + Send + Sync + 'static) -> App {
+    let mut app = App::new();
+    // Usually initialized by `DefaultPlugins`
+    app.init_schedule(PreStartup)
+        .init_schedule(Startup)
+        .init_schedule(PostStartup);
+    app.add_plugins(SimpleSubsecondPlugin::default())
+        .init_resource::<Runs>()
+        .with_hot_patch(section);
+    app
+}
+
+/// Applies the hot-patched sections again, as if the code had been patched.
+fn hot_patch(app: &mut App) {
+    app.world_mut().send_event_default::<HotPatched>();
+    app.update();
+}
+
+#[test]
+fn reapplied_section_replaces_its_systems() {
+    let mut app = app(|app: &mut App| {
+        app.add_systems(Update, count_runs);
+    });
+    app.update();
+    assert_eq!(app.world().resource::<Runs>().0, 1);
+
+    hot_patch(&mut app);
+    hot_patch(&mut app);
+    app.update();
+    // The systems of previous applications no longer run
+    assert_eq!(app.world().resource::<Runs>().0, 4);
+}
+
+#[test]
+fn section_can_add_systems_to_first() {
+    let mut app = app(|app: &mut App| {
+        app.add_systems(First, count_runs);
+    });
+    app.update();
+    assert_eq!(app.world().resource::<Runs>().0, 1);
+
+    hot_patch(&mut app);
+    app.update();
+    assert_eq!(app.world().resource::<Runs>().0, 3);
+}
+
+#[derive(Resource, Default)]
+struct Order(Vec<&'static str>);
+
+fn a(mut order: ResMut<Order>) {
+    order.0.push("a");
+}
+
+fn b(mut order: ResMut<Order>) {
+    order.0.push("b");
+}
+
+fn x() {}
+
+#[test]
+fn removed_systems_no_longer_order_other_systems() {
+    let ordered = Arc::new(AtomicBool::new(true));
+    let section_ordered = ordered.clone();
+    let mut app = app(move |app: &mut App| {
+        if section_ordered.load(Ordering::Relaxed) {
+            app.add_systems(Update, x.after(a).before(b));
+        } else {
+            app.add_systems(Update, x);
+        }
+    });
+    app.init_resource::<Order>().add_systems(Update, (a, b));
+    app.update();
+    let ambiguous = |app: &mut App| {
+        let schedule = app.get_schedule(Update).unwrap();
+        let name = |node| {
+            schedule
+                .systems()
+                .unwrap()
+                .find(|(id, _)| *id == node)
+                .map(|(_, system)| system.name())
+                .unwrap()
+        };
+        schedule
+            .graph()
+            .conflicting_systems()
+            .iter()
+            .any(|(first, second, _)| {
+                let mut names = [name(*first), name(*second)];
+                names.sort();
+                names[0].ends_with("::a") && names[1].ends_with("::b")
+            })
+    };
+    assert!(!ambiguous(&mut app));
+
+    // The previous `x` is still in the schedule, but no longer orders `a` before `b`
+    ordered.store(false, Ordering::Relaxed);
+    hot_patch(&mut app);
+    assert!(ambiguous(&mut app));
+}
+
+#[derive(Resource, Default)]
+struct Counted(usize);
+
+fn count_locally(mut count: Local<usize>, mut counted: ResMut<Counted>) {
+    *count += 1;
+    counted.0 = *count;
+}
+
+fn count_locally_with_runs(mut count: Local<usize>, mut counted: ResMut<Counted>, _: Res<Runs>) {
+    *count += 1;
+    counted.0 = *count;
+}
+
+#[test]
+fn locals_survive_reapplication_and_reset_when_the_parameters_change() {
+    let same_parameters = Arc::new(AtomicBool::new(true));
+    let section_parameters = same_parameters.clone();
+    let mut app = app(move |app: &mut App| {
+        if section_parameters.load(Ordering::Relaxed) {
+            app.add_systems(Update, count_locally);
+        } else {
+            app.add_systems(Update, count_locally_with_runs);
+        }
+    });
+    app.init_resource::<Counted>();
+    app.update();
+    app.update();
+    assert_eq!(app.world().resource::<Counted>().0, 2);
+
+    hot_patch(&mut app);
+    assert_eq!(app.world().resource::<Counted>().0, 3);
+
+    same_parameters.store(false, Ordering::Relaxed);
+    hot_patch(&mut app);
+    assert_eq!(app.world().resource::<Counted>().0, 1);
+}
+
+#[test]
+fn closures_reset_their_locals() {
+    let mut app = app(|app: &mut App| {
+        app.add_systems(
+            Update,
+            |mut count: Local<usize>, mut counted: ResMut<Counted>| {
+                *count += 1;
+                counted.0 = *count;
+            },
+        );
+    });
+    app.init_resource::<Counted>();
+    app.update();
+    app.update();
+    assert_eq!(app.world().resource::<Counted>().0, 2);
+
+    // A closure may capture other values after a hot patch, so it starts over
+    hot_patch(&mut app);
+    assert_eq!(app.world().resource::<Counted>().0, 1);
+}
+
+#[derive(Event, Default)]
+struct Scored;
+
+#[derive(Resource)]
+struct Enabled;
+
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+struct ScoreSet;
+
+fn read_scores(mut scores: EventReader<Scored>, mut runs: ResMut<Runs>) {
+    runs.0 += scores.read().count();
+}
+
+struct ScorePlugin;
+
+impl HotPlugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Scored>()
+            .configure_sets(Update, ScoreSet.run_if(resource_exists::<Enabled>))
+            .add_systems(Update, read_scores.in_set(ScoreSet));
+    }
+}
+
+#[test]
+fn hot_plugin_keeps_its_events_and_set_conditions() {
+    let mut app = app(|_: &mut App| {});
+    app.add_hot_plugins(ScorePlugin).insert_resource(Enabled);
+    // The section is first applied on the first update
+    app.update();
+    app.world_mut().send_event_default::<Scored>();
+    app.update();
+    assert_eq!(app.world().resource::<Runs>().0, 1);
+
+    // Events sent before a hot patch are still read after it
+    app.world_mut().send_event_default::<Scored>();
+    hot_patch(&mut app);
+    assert_eq!(app.world().resource::<Runs>().0, 2);
+
+    // The events are still updated once per frame, which would drop them before they are read otherwise
+    app.world_mut().send_event_default::<Scored>();
+    app.update();
+    assert_eq!(app.world().resource::<Runs>().0, 3);
+
+    // The condition of the set still applies after the set was configured again
+    app.world_mut().remove_resource::<Enabled>();
+    app.world_mut().send_event_default::<Scored>();
+    app.update();
+    assert_eq!(app.world().resource::<Runs>().0, 3);
+}
+
+#[derive(Component, Reflect, Debug, PartialEq)]
+#[reflect(Component)]
+struct Pos(i32);
+
+/// Spawns an entity identified by `identity` in the section's `StartupRerunHotPatch` schedule,
+/// with the position stored in the returned value.
+fn reconciled_app(identity: impl Bundle + Clone) -> (App, Arc<AtomicI32>) {
+    let spawned = Arc::new(AtomicI32::new(0));
+    let section_spawned = spawned.clone();
+    let mut app = app(move |app: &mut App| {
+        let spawned = section_spawned.clone();
+        let identity = identity.clone();
+        app.add_systems(StartupRerunHotPatch, move |mut commands: Commands| {
+            commands.spawn((identity.clone(), Pos(spawned.load(Ordering::Relaxed))));
+        });
+    });
+    app.register_type::<Pos>()
+        .insert_resource(HotRerunSettings { reconcile: true });
+    app.update();
+    (app, spawned)
+}
+
+fn positions(app: &mut App) -> Vec<(Entity, i32)> {
+    app.world_mut()
+        .query::<(Entity, &Pos)>()
+        .iter(app.world())
+        .map(|(entity, pos)| (entity, pos.0))
+        .collect()
+}
+
+fn check_reconciled(mut app: App, spawned: Arc<AtomicI32>) {
+    let [(entity, 0)] = positions(&mut app)[..] else {
+        panic!("{:?}", positions(&mut app));
+    };
+    app.world_mut().get_mut::<Pos>(entity).unwrap().0 = 5;
+
+    // The entity is kept along with the state it gained at runtime
+    hot_patch(&mut app);
+    assert_eq!(positions(&mut app), [(entity, 5)]);
+    hot_patch(&mut app);
+    assert_eq!(positions(&mut app), [(entity, 5)]);
+
+    // Components whose spawned value changed are updated
+    spawned.store(1, Ordering::Relaxed);
+    hot_patch(&mut app);
+    assert_eq!(positions(&mut app), [(entity, 1)]);
+}
+
+#[test]
+fn reconcile_keeps_entities_with_a_key() {
+    let (app, spawned) = reconciled_app(HotRerunKey("player".into()));
+    check_reconciled(app, spawned);
+}
+
+#[test]
+fn reconcile_keeps_entities_with_a_name() {
+    let (app, spawned) = reconciled_app(Name::new("player"));
+    check_reconciled(app, spawned);
+}
+
+#[derive(Component)]
+struct Tile;
+
+fn spawn_tiles(mut commands: Commands) {
+    commands.spawn(Tile).with_child(Tile);
+}
+
+/// The tiles spawned by a rerun, and the other tiles.
+fn tiles(app: &mut App) -> (Vec<Entity>, Vec<Entity>) {
+    let mut tiles = app
+        .world_mut()
+        .query_filtered::<(Entity, Has<SpawnedByHotRerun>), With<Tile>>();
+    let (rerun, other): (Vec<_>, Vec<_>) = tiles
+        .iter(app.world())
+        .partition(|(_, spawned_by_rerun)| *spawned_by_rerun);
+    let entities = |tiles: Vec<(Entity, bool)>| tiles.into_iter().map(|(entity, _)| entity);
+    (entities(rerun).collect(), entities(other).collect())
+}
+
+#[test]
+fn rerun_despawns_only_the_entities_spawned_by_its_previous_run() {
+    let mut app = app(|app: &mut App| {
+        app.add_systems(StartupRerunHotPatch, spawn_tiles);
+    });
+    app.add_systems(Startup, spawn_tiles);
+    app.update();
+    let (rerun, kept) = tiles(&mut app);
+    assert_eq!((rerun.len(), kept.len()), (2, 2));
+
+    hot_patch(&mut app);
+    let (respawned, still_kept) = tiles(&mut app);
+    assert_eq!(respawned.len(), 2);
+    assert_eq!(still_kept, kept);
+    // Entities spawned with `Commands`, along with their children, are despawned
+    for entity in rerun {
+        assert!(app.world().get_entity(entity).is_err());
+    }
+
+    hot_patch(&mut app);
+    let (_, still_kept) = tiles(&mut app);
+    assert_eq!(still_kept, kept);
+    assert_eq!(app.world_mut().query::<&Tile>().iter(app.world()).len(), 4);
+}
+
+#[derive(Resource)]
+struct CountRuns(SystemId);
+
+fn count_scores(_: Trigger<Scored>, mut runs: ResMut<Runs>) {
+    runs.0 += 1;
+}
+
+#[test]
+fn reapplied_section_replaces_its_observers_and_one_shot_systems() {
+    let mut app = app(|app: &mut App| {
+        app.add_observer(count_scores);
+        let count_runs = app.register_system(count_runs);
+        app.insert_resource(CountRuns(count_runs));
+    });
+    app.update();
+    let first = app.world().resource::<CountRuns>().0;
+
+    hot_patch(&mut app);
+    hot_patch(&mut app);
+    app.world_mut().trigger(Scored);
+    assert_eq!(app.world().resource::<Runs>().0, 1);
+
+    let current = app.world().resource::<CountRuns>().0;
+    assert_ne!(current, first);
+    app.world_mut().run_system(current).unwrap();
+    assert_eq!(app.world().resource::<Runs>().0, 2);
+    assert!(app.world_mut().run_system(first).is_err());
+    assert_eq!(
+        app.world_mut()
+            .query_filtered::<(), With<Observer>>()
+            .iter(app.world())
+            .len(),
+        1
+    );
+}
+
+fn y() {}
+
+fn z() {}
+
+fn schedule_errors(app: &mut App) -> usize {
+    app.world_mut()
+        .resource_mut::<Events<HotReloadScheduleError>>()
+        .drain()
+        .count()
+}
+
+#[test]
+fn invalid_generations_are_not_applied() {
+    let cyclic = Arc::new(AtomicBool::new(false));
+    let section_cyclic = cyclic.clone();
+    let mut app = app(move |app: &mut App| {
+        app.add_systems(Update, count_runs);
+        if section_cyclic.load(Ordering::Relaxed) {
+            app.add_systems(Update, (y.before(z), z.before(y)));
+        } else {
+            // Ordering against a system is ambiguous once a hot patch added it again
+            app.add_systems(Update, (y, z.after(y)));
+        }
+    });
+    app.update();
+    assert_eq!(app.world().resource::<Runs>().0, 1);
+
+    // The previous systems keep running, and the schedule keeps being built
+    hot_patch(&mut app);
+    assert_eq!(schedule_errors(&mut app), 1);
+    cyclic.store(true, Ordering::Relaxed);
+    hot_patch(&mut app);
+    assert_eq!(schedule_errors(&mut app), 1);
+    app.update();
+    assert_eq!(app.world().resource::<Runs>().0, 4);
+    assert_eq!(schedule_errors(&mut app), 0);
+}
+
+fn count(mut counted: ResMut<Counted>) {
+    counted.0 += 1;
+}
+
+#[test]
+fn named_sections_are_hot_patched_independently() {
+    let cyclic = Arc::new(AtomicBool::new(false));
+    let section_cyclic = cyclic.clone();
+    let mut app = app(|_: &mut App| {});
+    app.init_resource::<Counted>()
+        .with_hot_patch_named("ui", move |app: &mut App| {
+            app.add_systems(Update, count_runs);
+            if section_cyclic.load(Ordering::Relaxed) {
+                app.add_systems(Update, (y.before(z), z.before(y)));
+            }
+        })
+        .with_hot_patch_named("player", |app: &mut App| {
+            app.add_systems(Update, count)
+                .add_systems(StartupRerunHotPatch, spawn_tiles);
+        });
+    app.update();
+    hot_patch(&mut app);
+    hot_patch(&mut app);
+    assert_eq!(app.world().resource::<Runs>().0, 3);
+    assert_eq!(app.world().resource::<Counted>().0, 3);
+    assert_eq!(app.world_mut().query::<&Tile>().iter(app.world()).len(), 2);
+
+    // A section that fails to apply doesn't keep the others from being applied
+    cyclic.store(true, Ordering::Relaxed);
+    hot_patch(&mut app);
+    assert_eq!(schedule_errors(&mut app), 1);
+    assert_eq!(app.world().resource::<Runs>().0, 4);
+    assert_eq!(app.world().resource::<Counted>().0, 4);
+    assert_eq!(app.world_mut().query::<&Tile>().iter(app.world()).len(), 2);
+}
+
+#[test]
+fn replaced_section_removes_what_it_no_longer_adds() {
+    let replaced = Arc::new(AtomicBool::new(false));
+    let section_replaced = replaced.clone();
+    let mut app = app(move |app: &mut App| {
+        if section_replaced.load(Ordering::Relaxed) {
+            app.add_systems(Update, count);
+        } else {
+            app.add_systems(Update, count_runs)
+                .add_systems(StartupRerunHotPatch, spawn_tiles)
+                .add_observer(count_scores);
+        }
+    });
+    app.init_resource::<Counted>();
+    app.update();
+    assert_eq!(app.world_mut().query::<&Tile>().iter(app.world()).len(), 2);
+
+    replaced.store(true, Ordering::Relaxed);
+    hot_patch(&mut app);
+    app.world_mut().trigger(Scored);
+    assert_eq!(app.world().resource::<Runs>().0, 1);
+    assert_eq!(app.world().resource::<Counted>().0, 1);
+    assert_eq!(app.world_mut().query::<&Tile>().iter(app.world()).len(), 0);
+}
+
+fn section_names(app: &mut App) -> Vec<&'static str> {
+    let mut names: Vec<_> = app
+        .world_mut()
+        .query::<&SpawnedByHotRerun>()
+        .iter(app.world())
+        .filter_map(|spawned_by| match spawned_by.0 {
+            HotRerunScope::HotPatchedSection(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn sections_are_named_after_where_they_are_added() {
+    let spawn = |app: &mut App| {
+        app.world_mut().spawn(Tile);
+    };
+    let mut first = app(spawn);
+    first.with_hot_patch(spawn);
+    first.update();
+    let mut second = app(spawn);
+    second.update();
+
+    let names = section_names(&mut first);
+    assert_eq!(names.len(), 2);
+    assert_ne!(names[0], names[1]);
+    assert!(names.iter().all(|name| name.contains("hot_patched_app.rs")));
+    // The same call names its sections the same, without turning its location into a name again
+    let [shared] = section_names(&mut second)[..] else {
+        panic!("{:?}", section_names(&mut second));
+    };
+    assert!(names.iter().any(|name| core::ptr::eq(*name, shared)));
+}
@@ -0,0 +1,314 @@
+use std::{any::TypeId, time::Duration};
+
+use bevy::{prelude::*, reflect::GetTypeRegistration};
+use bevy_simple_subsecond_system::prelude::*;
+
+/// Stands in for the definition of a component before a hot patch:
+/// its `current_type_id` points to [`NewPosition`], as if the struct had been edited.
+#[derive(Debug, Reflect, Component)]
+#[reflect(Component, HotPatchMigrate)]
+struct OldPosition {
+    x: f32,
+    y: f32,
+    label: String,
+}
+
+impl HotPatchMigrate for OldPosition {
+    fn current_type_id() -> TypeId {
+        TypeId::of::<NewPosition>()
+    }
+}
+
+#[derive(Debug, Reflect, Component, Default, PartialEq, HotPatchMigrate)]
+#[reflect(Component, Default, HotPatchMigrate)]
+struct NewPosition {
+    x: f32,
+    z: f32,
+    label: usize,
+}
+
+fn app<Old: GetTypeRegistration, New: GetTypeRegistration>() -> App {
+    let mut app = App::new();
+    app.add_plugins(SimpleSubsecondPlugin::default())
+        .register_type::<Old>()
+        .register_type::<New>();
+    app
+}
+
+fn migrated(app: &mut App) -> Vec<ComponentMigrated> {
+    app.world_mut()
+        .resource_mut::<Events<ComponentMigrated>>()
+        .drain()
+        .collect()
+}
+
+fn spawn_old_positions(app: &mut App) -> [Entity; 2] {
+    [1.0, 2.0].map(|x| {
+        app.world_mut()
+            .spawn(OldPosition {
+                x,
+                y: 3.0,
+                label: "old".to_string(),
+            })
+            .id()
+    })
+}
+
+#[test]
+fn dry_run_reports_without_migrating() {
+    let mut app = app::<OldPosition, NewPosition>();
+    app.insert_resource(ComponentMigrationSettings { dry_run: true });
+    let entities = spawn_old_positions(&mut app);
+
+    app.update();
+    let reports = migrated(&mut app);
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert!(report.dry_run);
+    assert!(report.from_name.ends_with("OldPosition"));
+    assert!(report.to_name.ends_with("NewPosition"));
+    assert_eq!(report.entities, entities);
+    assert_eq!(report.fields_added, ["z"]);
+    assert_eq!(report.fields_removed, ["y"]);
+    assert_eq!(report.fields_failed, ["label"]);
+    assert_eq!(report.duration, Duration::ZERO);
+
+    let world = app.world_mut();
+    for entity in entities {
+        let entity = world.entity(entity);
+        assert!(entity.contains::<OldPosition>());
+        assert!(!entity.contains::<NewPosition>());
+        assert!(!entity.contains::<Migrating>());
+    }
+
+    // A pending migration is only reported once
+    app.update();
+    assert!(migrated(&mut app).is_empty());
+}
+
+#[test]
+fn migration_reports_and_carries_over_fields() {
+    let mut app = app::<OldPosition, NewPosition>();
+    app.insert_resource(ComponentMigrationSettings { dry_run: true });
+    let entities = spawn_old_positions(&mut app);
+    app.update();
+    migrated(&mut app);
+
+    // Pending migrations are applied once the dry run is turned off
+    app.world_mut()
+        .resource_mut::<ComponentMigrationSettings>()
+        .dry_run = false;
+    app.update();
+    let reports = migrated(&mut app);
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert!(!report.dry_run);
+    assert_eq!(report.entities, entities);
+    assert_eq!(report.fields_added, ["z"]);
+    assert_eq!(report.fields_removed, ["y"]);
+    assert_eq!(report.fields_failed, ["label"]);
+
+    let world = app.world_mut();
+    for (entity, x) in entities.into_iter().zip([1.0, 2.0]) {
+        let entity = world.entity(entity);
+        assert!(!entity.contains::<OldPosition>());
+        assert!(!entity.contains::<Migrating>());
+        assert_eq!(
+            entity.get::<NewPosition>(),
+            Some(&NewPosition {
+                x,
+                z: 0.0,
+                label: 0
+            })
+        );
+    }
+
+    app.update();
+    assert!(migrated(&mut app).is_empty());
+}
+
+#[derive(Component, Debug, PartialEq)]
+struct Marker(u32);
+
+#[test]
+fn migration_keeps_entities_in_different_archetypes_intact() {
+    let mut app = app::<OldPosition, NewPosition>();
+    let old = || OldPosition {
+        x: 1.0,
+        y: 2.0,
+        label: "old".to_string(),
+    };
+    let world = app.world_mut();
+    let entities = [
+        world.spawn(old()).id(),
+        world.spawn((old(), Marker(1))).id(),
+        world.spawn((old(), Name::new("named"))).id(),
+        world.spawn((old(), Marker(2))).id(),
+    ];
+    app.update();
+
+    let reports = migrated(&mut app);
+    assert_eq!(reports.len(), 1);
+    let mut reported = reports[0].entities.clone();
+    reported.sort();
+    let mut expected = entities.to_vec();
+    expected.sort();
+    assert_eq!(reported, expected);
+
+    let world = app.world();
+    for entity in entities {
+        assert!(!world.entity(entity).contains::<OldPosition>());
+        assert!(world.entity(entity).contains::<NewPosition>());
+    }
+    assert_eq!(world.get::<Marker>(entities[1]), Some(&Marker(1)));
+    assert_eq!(world.get::<Marker>(entities[3]), Some(&Marker(2)));
+    assert_eq!(
+        world.get::<Name>(entities[2]).map(Name::as_str),
+        Some("named")
+    );
+}
+
+#[derive(Debug, Reflect, Component)]
+#[reflect(Component, HotPatchMigrate)]
+struct OldFollow {
+    target: u32,
+    speed: f32,
+}
+
+impl HotPatchMigrate for OldFollow {
+    fn current_type_id() -> TypeId {
+        TypeId::of::<NewFollow>()
+    }
+}
+
+/// Has no `Default`, so every field that can't be carried over needs `#[hot_migrate(default = ...)]`.
+#[derive(Debug, Reflect, Component, PartialEq, HotPatchMigrate)]
+#[reflect(Component, HotPatchMigrate)]
+struct NewFollow {
+    target: u32,
+    #[hot_migrate(default = 5.0)]
+    distance: f32,
+    #[hot_migrate(default = 7)]
+    speed: u32,
+}
+
+#[test]
+fn migration_without_default_uses_field_defaults() {
+    let mut app = app::<OldFollow, NewFollow>();
+    let entity = app
+        .world_mut()
+        .spawn(OldFollow {
+            target: 3,
+            speed: 1.5,
+        })
+        .id();
+    app.update();
+
+    let reports = migrated(&mut app);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].entities, [entity]);
+    assert_eq!(reports[0].fields_added, ["distance"]);
+    assert_eq!(reports[0].fields_failed, ["speed"]);
+    let entity = app.world().entity(entity);
+    assert!(!entity.contains::<OldFollow>());
+    assert_eq!(
+        entity.get::<NewFollow>(),
+        Some(&NewFollow {
+            target: 3,
+            distance: 5.0,
+            speed: 7,
+        })
+    );
+}
+
+#[derive(Debug, Reflect, Component)]
+#[reflect(Component, HotPatchMigrate)]
+struct OldHealth(f32);
+
+impl HotPatchMigrate for OldHealth {
+    fn current_type_id() -> TypeId {
+        TypeId::of::<NewHealth>()
+    }
+}
+
+/// Neither `Default` nor a `#[hot_migrate(default = ...)]` for the retyped field.
+#[derive(Debug, Reflect, Component, HotPatchMigrate)]
+#[reflect(Component, HotPatchMigrate)]
+struct NewHealth(u32);
+
+#[test]
+fn migration_without_any_default_keeps_the_old_component() {
+    let mut app = app::<OldHealth, NewHealth>();
+    let entity = app.world_mut().spawn(OldHealth(1.0)).id();
+    app.update();
+
+    let reports = migrated(&mut app);
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].entities.is_empty());
+    assert_eq!(reports[0].fields_failed, ["0"]);
+    assert!(app.world().entity(entity).contains::<OldHealth>());
+    assert!(!app.world().entity(entity).contains::<NewHealth>());
+
+    // The failed migration is not attempted again every frame
+    app.update();
+    app.update();
+    assert!(migrated(&mut app).is_empty());
+    assert!(app.world().entity(entity).contains::<OldHealth>());
+}
+
+/// Opaque to reflection, and can neither be cloned nor built through it.
+#[derive(Debug, Reflect, Clone, PartialEq)]
+#[reflect(opaque, from_reflect = false)]
+struct Handle(u32);
+
+impl FromReflect for Handle {
+    fn from_reflect(reflect: &dyn PartialReflect) -> Option<Self> {
+        reflect.try_downcast_ref().cloned()
+    }
+}
+
+#[derive(Debug, Reflect, Component)]
+#[reflect(Component, HotPatchMigrate)]
+struct OldTarget {
+    handle: Handle,
+    range: f32,
+}
+
+impl HotPatchMigrate for OldTarget {
+    fn current_type_id() -> TypeId {
+        TypeId::of::<NewTarget>()
+    }
+}
+
+#[derive(Debug, Reflect, Component, PartialEq, HotPatchMigrate)]
+#[reflect(Component, HotPatchMigrate)]
+struct NewTarget {
+    #[hot_migrate(default = Handle(0))]
+    handle: Handle,
+    range: f32,
+}
+
+#[test]
+fn migration_uses_the_default_of_fields_that_cant_be_copied() {
+    let mut app = app::<OldTarget, NewTarget>();
+    let entity = app
+        .world_mut()
+        .spawn(OldTarget {
+            handle: Handle(3),
+            range: 2.0,
+        })
+        .id();
+    app.update();
+
+    let reports = migrated(&mut app);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].entities, [entity]);
+    assert_eq!(reports[0].fields_failed, ["handle"]);
+    assert_eq!(
+        app.world().get::<NewTarget>(entity),
+        Some(&NewTarget {
+            handle: Handle(0),
+            range: 2.0,
+        })
+    );
+}
@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_simple_subsecond_system::{
+    patch_source::{ChannelPatchSource, DevserverMsg},
+    prelude::*,
+};
+
+/// Updates the app until an event of type `E` was sent, as messages are forwarded from another thread.
+fn update_until<E: Event>(app: &mut App) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        app.update();
+        if !app.world().resource::<Events<E>>().is_empty() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    false
+}
+
+#[test]
+fn channel_source_forwards_build_messages() {
+    let (sender, source) = ChannelPatchSource::new();
+    let mut app = App::new();
+    app.add_plugins(SimpleSubsecondPlugin::default().with_patch_source(source));
+
+    sender.send(DevserverMsg::HotPatchStart).unwrap();
+    assert!(update_until::<HotBuildStarted>(&mut app));
+
+    sender.send(DevserverMsg::FullReloadFailed).unwrap();
+    assert!(update_until::<HotBuildFailed>(&mut app));
+    assert!(app.world().resource::<Events<HotPatched>>().is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_source_only_replaces_sockets() {
+    use std::{
+        io::Write,
+        os::unix::net::{UnixListener, UnixStream},
+    };
+
+    use bevy_simple_subsecond_system::patch_source::UnixSocketPatchSource;
+
+    let app_with_socket = |path| {
+        let mut app = App::new();
+        app.add_plugins(
+            SimpleSubsecondPlugin::default().with_patch_source(UnixSocketPatchSource::new(path)),
+        );
+        app
+    };
+    let dir = std::env::temp_dir();
+    let id = std::process::id();
+
+    // A file that is not a socket is kept
+    let file = dir.join(format!("bevy_simple_subsecond_system-{id}.txt"));
+    std::fs::write(&file, "keep me").unwrap();
+    app_with_socket(file.clone()).update();
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+    std::fs::remove_file(&file).unwrap();
+
+    // A socket left over from a previous run is replaced
+    let socket = dir.join(format!("bevy_simple_subsecond_system-{id}.sock"));
+    drop(UnixListener::bind(&socket).unwrap());
+    let mut app = app_with_socket(socket.clone());
+    let mut stream = UnixStream::connect(&socket).unwrap();
+    writeln!(stream, "\"HotPatchStart\"").unwrap();
+    assert!(update_until::<HotBuildStarted>(&mut app));
+    std::fs::remove_file(&socket).unwrap();
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+use bevy_simple_subsecond_system::prelude::*;
+
+#[derive(Component)]
+struct Player;
+
+#[derive(Component)]
+struct Enemy;
+
+#[hot]
+fn count<T: Component>(query: Query<&T>) {
+    let _ = query.iter().count();
+}
+
+// Systems register themselves in a static, so all apps in this test binary would share them
+#[test]
+fn generic_systems_are_registered_per_instantiation() {
+    let mut app = App::new();
+    app.add_plugins(SimpleSubsecondPlugin::default())
+        .add_systems(Update, (count::<Player>, count::<Enemy>));
+    app.update();
+    app.update();
+
+    let registry = app.world().resource::<HotSystemRegistry>();
+    let mut names: Vec<_> = registry.iter().map(|system| system.name).collect();
+    names.sort();
+    assert_eq!(names.len(), 2, "{names:?}");
+    assert!(names[0].ends_with("count<registry::Enemy>"), "{names:?}");
+    assert!(names[1].ends_with("count<registry::Player>"), "{names:?}");
+    assert!(
+        registry
+            .iter()
+            .all(|system| system.mode == HotSystemMode::Plain)
+    );
+}
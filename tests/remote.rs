@@ -0,0 +1,70 @@
+#![cfg(feature = "remote")]
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_remote::{RemoteMethodSystemId, RemoteMethods, RemotePlugin};
+use bevy_simple_subsecond_system::{
+    patch_source::{ChannelPatchSource, DevserverMsg},
+    prelude::*,
+    remote::STATUS_METHOD,
+};
+use serde_json::Value;
+
+fn call(app: &mut App, method: &str) -> Value {
+    let RemoteMethodSystemId::Instant(system) =
+        *app.world().resource::<RemoteMethods>().get(method).unwrap()
+    else {
+        panic!("{method} is not an instant method");
+    };
+    app.world_mut()
+        .run_system_with(system, None)
+        .unwrap()
+        .unwrap()
+}
+
+/// Updates the app until `hot/status` reports what `done` waits for, as messages are delivered from another thread.
+fn status_until(app: &mut App, done: impl Fn(&Value) -> bool) -> Value {
+    let start = Instant::now();
+    loop {
+        app.update();
+        let status = call(app, STATUS_METHOD);
+        if done(&status) || start.elapsed() > Duration::from_secs(5) {
+            return status;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn status_reports_the_messages_delivered_by_the_patch_source() {
+    let (sender, source) = ChannelPatchSource::new();
+    let mut app = App::new();
+    app.add_plugins((
+        RemotePlugin::default(),
+        SimpleSubsecondPlugin::default().with_patch_source(source),
+    ));
+    // Usually called by `App::run`, the methods are registered once all plugins were added
+    app.finish();
+    app.cleanup();
+
+    app.update();
+    let status = call(&mut app, STATUS_METHOD);
+    assert_eq!(status["messages_received"], 0);
+    assert_eq!(status["connection"], "waiting");
+    assert_eq!(status["last_message"], Value::Null);
+
+    sender.send(DevserverMsg::HotPatchStart).unwrap();
+    let status = status_until(&mut app, |status| status["building"] == true);
+    assert_eq!(status["messages_received"], 1);
+    assert_eq!(status["connection"], "receiving");
+    assert_eq!(status["building"], true);
+    assert!(status["last_message"].is_f64());
+
+    sender.send(DevserverMsg::FullReloadFailed).unwrap();
+    let status = status_until(&mut app, |status| !status["last_failure"].is_null());
+    assert_eq!(status["messages_received"], 2);
+    assert_eq!(status["building"], false);
+    assert_eq!(status["last_failure"], "build failed");
+    assert_eq!(status["last_patch"], Value::Null);
+}
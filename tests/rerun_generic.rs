@@ -0,0 +1,65 @@
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+use bevy_simple_subsecond_system::prelude::*;
+
+struct Player;
+
+struct Enemy;
+
+#[derive(Resource)]
+struct Runs<T>(usize, PhantomData<T>);
+
+impl<T> Default for Runs<T> {
+    fn default() -> Self {
+        Self(0, PhantomData)
+    }
+}
+
+#[hot(rerun_on_hot_patch = true)]
+fn count_runs<T: Send + Sync + 'static>(mut runs: ResMut<Runs<T>>) {
+    runs.0 += 1;
+}
+
+fn runs<T: Send + Sync + 'static>(app: &App) -> usize {
+    app.world().resource::<Runs<T>>().0
+}
+
+// Systems register themselves in a static, so all apps in this test binary would share them
+#[test]
+fn each_instantiation_of_a_generic_system_is_rerun() {
+    let mut app = App::new();
+    app.add_plugins(SimpleSubsecondPlugin::default())
+        .init_resource::<Runs<Player>>()
+        .init_resource::<Runs<Enemy>>()
+        .add_systems(Update, (count_runs::<Player>, count_runs::<Enemy>));
+    app.update();
+    app.update();
+    assert_eq!((runs::<Player>(&app), runs::<Enemy>(&app)), (2, 2));
+
+    let names: Vec<_> = app
+        .world()
+        .resource::<HotSystemRegistry>()
+        .iter()
+        .map(|system| system.name)
+        .collect();
+    let player = *names
+        .iter()
+        .find(|name| name.ends_with("<rerun_generic::Player>"))
+        .unwrap();
+    let enemy = *names
+        .iter()
+        .find(|name| name.ends_with("<rerun_generic::Enemy>"))
+        .unwrap();
+    // Every instantiation registers itself each time it runs, but is only added once
+    assert_eq!(names.len(), 2);
+    assert_ne!(player, enemy);
+
+    assert!(HotSystemRegistry::rerun(app.world_mut(), player));
+    assert_eq!((runs::<Player>(&app), runs::<Enemy>(&app)), (3, 2));
+    assert!(HotSystemRegistry::rerun(app.world_mut(), enemy));
+    assert_eq!((runs::<Player>(&app), runs::<Enemy>(&app)), (3, 3));
+
+    app.update();
+    assert_eq!(app.world().resource::<HotSystemRegistry>().len(), 2);
+}
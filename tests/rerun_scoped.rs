@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use bevy_simple_subsecond_system::prelude::*;
+
+#[derive(Resource)]
+struct InsertBoard(bool);
+
+#[derive(Resource)]
+struct Board;
+
+#[derive(Event, Default)]
+struct Moved;
+
+#[hot(rerun_on_hot_patch = true, despawn_spawned = true)]
+fn setup(mut commands: Commands, insert_board: Res<InsertBoard>) {
+    if insert_board.0 {
+        commands.insert_resource(Board);
+    }
+    commands.init_resource::<Events<Moved>>();
+}
+
+// Systems register themselves in a static, so all apps in this test binary would share them
+#[test]
+fn rerun_removes_the_resources_inserted_by_its_previous_run() {
+    let mut app = App::new();
+    app.add_plugins(SimpleSubsecondPlugin::default())
+        .insert_resource(InsertBoard(true))
+        .add_systems(Startup, setup);
+    app.update();
+    assert!(app.world().contains_resource::<Board>());
+    app.world_mut().send_event_default::<Moved>();
+
+    let name = app
+        .world()
+        .resource::<HotSystemRegistry>()
+        .iter()
+        .find(|system| system.name.ends_with("::setup"))
+        .unwrap()
+        .name;
+    app.world_mut().resource_mut::<InsertBoard>().0 = false;
+    assert!(HotSystemRegistry::rerun(app.world_mut(), name));
+    assert!(!app.world().contains_resource::<Board>());
+    // Resources that existed before the first run are kept
+    assert!(app.world().contains_resource::<InsertBoard>());
+    // Events are kept along with the events sent since the previous run
+    assert_eq!(app.world().resource::<Events<Moved>>().len(), 1);
+
+    app.world_mut().resource_mut::<InsertBoard>().0 = true;
+    assert!(HotSystemRegistry::rerun(app.world_mut(), name));
+    assert!(app.world().contains_resource::<Board>());
+    assert_eq!(app.world().resource::<Events<Moved>>().len(), 1);
+}